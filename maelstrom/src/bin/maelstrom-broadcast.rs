@@ -1,66 +1,642 @@
-use std::{collections::BTreeSet, thread::scope};
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    env,
+    thread::{scope, sleep, Scope},
+    time::{Duration, Instant},
+};
 
-use gossip_glomers::Node;
+use gossip_glomers::{detector, fnv1a, iblt::Iblt, topology::Topology, Err, Node};
 use parking_lot::{Mutex, RwLock};
-use serde_json::json;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 
-fn main() {
-    let msgs = Mutex::new(BTreeSet::<u64>::new());
-    let neighbours = RwLock::new(Vec::<String>::new());
+/// How new messages reach the neighbours, named in lowercase by
+/// `$BROADCAST_MODE`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Send each new message to the neighbours right away
+    Flood,
+    /// Buffer new messages per neighbour, flushed as one `gossip` per interval
+    Batch(Duration),
+}
+
+/// Which nodes are neighbours, named in lowercase by `$BROADCAST_OVERLAY`,
+/// followed by `k` if any
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Overlay {
+    /// Those of the `topology` message
+    Maelstrom,
+    /// Every node linked to the first one
+    Star,
+    /// Tree with `k` children per node, of depth two if unset
+    Tree(Option<usize>),
+    /// Random graph with `k` neighbours per node
+    Expander(usize),
+}
+
+impl Overlay {
+    /// Graph built from the node ids alone, the same on every node, None to
+    /// follow the `topology` message
+    fn build(self, ids: &[String]) -> Option<Topology> {
+        match self {
+            Overlay::Maelstrom => None,
+            Overlay::Star => Some(Topology::star(ids, &ids[0])),
+            Overlay::Tree(Some(k)) => Some(Topology::tree(ids, k)),
+            Overlay::Tree(None) => Some(Topology::two_level(ids)),
+            // Too few nodes for k neighbours each, link them all
+            Overlay::Expander(k) if k + 1 >= ids.len() || !(ids.len() * k).is_multiple_of(2) => {
+                let mut tmp = Topology::empty(ids);
+                for a in ids {
+                    for b in ids {
+                        tmp.link(a, b);
+                    }
+                }
+                Some(tmp)
+            }
+            Overlay::Expander(k) => Some(Topology::random_regular(ids, k, 0)),
+        }
+    }
+}
+
+/// Delay before retrying a flooded message the peer did not acknowledge
+const RETRY: Duration = Duration::from_millis(100);
+/// Longest delay between two retries to a peer
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Broadcast with the client-provided id it is deduplicated by, if any,
+/// the form nodes exchange and log messages in
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Message {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<Value>,
+    message: Value,
+}
+
+impl Message {
+    /// Deduplication key, the same on every node: the id if any, the
+    /// content otherwise, as canonical JSON
+    fn key(&self) -> String {
+        let tagged = match &self.id {
+            Some(id) => json!({"id": id}),
+            None => json!({"message": self.message}),
+        };
+        tagged.to_string()
+    }
+}
+
+/// Hash of a key in the tables of a reconciliation, seeded anew each time
+/// so that keys colliding once are told apart the next
+fn digest(seed: u64, key: &str) -> u64 {
+    fnv1a(&[&seed.to_le_bytes(), key.as_bytes()].concat())
+}
+
+/// Cells of the first table sent to reconcile with a peer, enough for a
+/// difference of about 10 messages
+const SYNC_CELLS: usize = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Config {
+    mode: Mode,
+    overlay: Overlay,
+    /// Delay between two reconciliations with a random neighbour, None if
+    /// set to 0
+    sync: Option<Duration>,
+    /// Heartbeat interval of the failure detector, whose heartbeats are
+    /// messages too, None if set to 0 as by default
+    detector: Option<Duration>,
+    /// Messages queued per peer, beyond which the oldest are left to the
+    /// sync, and lost to that peer with the sync disabled
+    outbox_limit: usize,
+}
+
+impl Config {
+    /// From the `$BROADCAST_` variables `MODE`, `INTERVAL_MS`, `OVERLAY`,
+    /// `SYNC_MS`, `DETECTOR_MS` and `OUTBOX_LIMIT`
+    fn from_env() -> Result<Self, String> {
+        let num = |name: &str, default: u64| -> Result<u64, String> {
+            match env::var(name) {
+                Ok(ms) => ms
+                    .parse()
+                    .map_err(|e| format!("invalid {name} {ms:?}: {e}")),
+                Err(_) => Ok(default),
+            }
+        };
+        let interval = match num("BROADCAST_INTERVAL_MS", 200)? {
+            0 => return Err("BROADCAST_INTERVAL_MS must be positive".to_owned()),
+            ms => ms,
+        };
+        let sync = Some(num("BROADCAST_SYNC_MS", 1000)?)
+            .filter(|ms| *ms > 0)
+            .map(Duration::from_millis);
+        let detector = Some(num("BROADCAST_DETECTOR_MS", 0)?)
+            .filter(|ms| *ms > 0)
+            .map(Duration::from_millis);
+        let outbox_limit = num("BROADCAST_OUTBOX_LIMIT", 10_000)? as usize;
+        let mode = match env::var("BROADCAST_MODE").as_deref() {
+            Ok("flood") | Err(_) => Mode::Flood,
+            Ok("batch") => Mode::Batch(Duration::from_millis(interval)),
+            Ok(mode) => return Err(format!("unknown BROADCAST_MODE {mode:?}")),
+        };
+        let overlay = env::var("BROADCAST_OVERLAY").unwrap_or_else(|_| "maelstrom".to_owned());
+        let k = |prefix: &str| -> Result<Option<usize>, String> {
+            let k = &overlay[prefix.len()..];
+            match k {
+                "" => Ok(None),
+                k => match k.parse() {
+                    Ok(k) if k > 0 => Ok(Some(k)),
+                    _ => Err(format!("invalid BROADCAST_OVERLAY {overlay:?}")),
+                },
+            }
+        };
+        let overlay = match overlay.as_str() {
+            "maelstrom" => Overlay::Maelstrom,
+            "star" => Overlay::Star,
+            o if o.starts_with("tree") => Overlay::Tree(k("tree")?),
+            o if o.starts_with("expander") => Overlay::Expander(k("expander")?.unwrap_or(4)),
+            o => return Err(format!("unknown BROADCAST_OVERLAY {o:?}")),
+        };
+        Ok(Self {
+            mode,
+            overlay,
+            sync,
+            detector,
+            outbox_limit,
+        })
+    }
+}
 
+/// Messages in the order they arrived, each known locally by its position
+///
+/// The order only grows, which makes positions in it cursors for
+/// incremental reads, stable across restarts as the log replays it. Other
+/// nodes number messages differently, and tell them apart by key instead.
+#[derive(Debug, Default)]
+struct Messages {
+    order: Vec<Message>,
+    /// Position of each message by key
+    by_key: BTreeMap<String, u64>,
+}
+
+impl Messages {
+    /// Add a message unless we have it, returning its position and whether
+    /// it is new
+    fn insert(&mut self, message: Message) -> (u64, bool) {
+        let key = message.key();
+        if let Some(pos) = self.by_key.get(&key) {
+            return (*pos, false);
+        }
+        let pos = self.order.len() as u64;
+        self.by_key.insert(key, pos);
+        self.order.push(message);
+        (pos, true)
+    }
+
+    /// Messages arrived past the cursor `after`, and the cursor to continue from
+    fn since(&self, after: usize) -> (Vec<&Value>, usize) {
+        let messages = self.order.get(after..).unwrap_or_default();
+        (
+            messages.iter().map(|m| &m.message).collect(),
+            self.order.len(),
+        )
+    }
+
+    fn batch<'a>(&self, positions: impl IntoIterator<Item = &'a u64>) -> Vec<Message> {
+        let messages = positions.into_iter().map(|p| &self.order[*p as usize]);
+        messages.cloned().collect()
+    }
+
+    /// Positions of the messages by digest under `seed`
+    fn digests(&self, seed: u64) -> BTreeMap<u64, Vec<u64>> {
+        let mut digests: BTreeMap<u64, Vec<u64>> = BTreeMap::new();
+        for (key, pos) in &self.by_key {
+            digests.entry(digest(seed, key)).or_default().push(*pos);
+        }
+        digests
+    }
+}
+
+fn main() {
+    let Config {
+        mode,
+        overlay,
+        sync,
+        detector,
+        outbox_limit,
+    } = Config::from_env().unwrap();
     let node = &Node::new();
-    node.run(|mut msg| match msg.body["type"].as_str().unwrap() {
-        "broadcast" => {
-            let message = msg.body["message"].as_u64().unwrap();
-            if msgs.lock().insert(message) {
-                scope(|s| {
-                    for id in neighbours.read().clone() {
-                        if id != msg.src {
-                            s.spawn(move || loop {
-                                if node
-                                    .rpc(
-                                        id.clone(),
-                                        json!({
-                                            "type": "broadcast",
-                                            "message": message
-                                        }),
-                                    )
-                                    .is_ok()
-                                {
-                                    break;
-                                }
-                            });
+    // Messages are logged before being acknowledged, and the topology saved,
+    // for a restarted node to resume where it crashed
+    let (log, logged) = node.storage.log::<Message>("messages").unwrap();
+    let log = Mutex::new(log);
+    let mut messages = Messages::default();
+    for message in logged {
+        messages.insert(message);
+    }
+    let msgs = Mutex::new(messages);
+    let overlay = overlay.build(&node.node_ids);
+    let topology: Option<Vec<String>> = match &overlay {
+        Some(overlay) => Some(overlay.neighbours(&node.id).cloned().collect()),
+        None => node.storage.load("topology").unwrap(),
+    };
+    let neighbours = RwLock::new(topology.clone().unwrap_or_default());
+    let base = match mode {
+        Mode::Flood => RETRY,
+        Mode::Batch(interval) => interval,
+    };
+    let outbox = &Outbox::new(base, outbox_limit);
+
+    // Watch no one until the neighbours are known, rather than heartbeat
+    // every node meanwhile
+    let detector = detector.map(|interval| {
+        let config = detector::Config {
+            interval,
+            ..Default::default()
+        };
+        let detector = node.enable_detector(config);
+        detector.watch(topology.iter().flatten());
+        detector
+    });
+
+    // Keep the messages not seen before, logged before returning the
+    // positions of all of them and of the new ones
+    let record = |batch: Vec<Message>| -> (Vec<u64>, Vec<u64>) {
+        let mut msgs = msgs.lock();
+        let (mut positions, mut new) = (Vec::new(), Vec::new());
+        for message in batch {
+            let (pos, added) = msgs.insert(message);
+            positions.push(pos);
+            if added {
+                new.push(pos);
+            }
+        }
+        if !new.is_empty() {
+            log.lock()
+                .append_all(new.iter().map(|p| &msgs.order[*p as usize]))
+                .unwrap();
+        }
+        (positions, new)
+    };
+    // Every neighbour but the one messages came from, and with our own
+    // overlay, the nodes behind a suspected neighbour to route around it
+    let targets = |from: &str| -> BTreeSet<String> {
+        let mut targets = BTreeSet::new();
+        for id in neighbours.read().iter().filter(|id| *id != from) {
+            targets.insert(id.clone());
+            if let Some(overlay) = overlay.as_ref().filter(|_| !node.is_alive(id)) {
+                let behind = overlay.neighbours(id);
+                targets.extend(behind.filter(|b| **b != node.id && *b != from).cloned());
+            }
+        }
+        targets
+    };
+    let enqueue = |new: &[u64], from: &str| {
+        if !new.is_empty() {
+            for id in targets(from) {
+                outbox.push(&id, new.iter().copied());
+            }
+        }
+    };
+
+    scope(|s| {
+        let msgs = &msgs;
+        s.spawn(move || {
+            while !node.is_closed() {
+                sleep(outbox.base);
+                flush(s, node, outbox, msgs);
+            }
+        });
+        if let Some(interval) = sync {
+            let (neighbours, record, enqueue) = (&neighbours, &record, &enqueue);
+            s.spawn(move || {
+                while !node.is_closed() {
+                    sleep(interval);
+                    let peers: Vec<String> = neighbours
+                        .read()
+                        .iter()
+                        .filter(|id| node.is_alive(id))
+                        .cloned()
+                        .collect();
+                    if peers.is_empty() {
+                        continue;
+                    }
+                    let peer = &peers[fastrand::usize(..peers.len())];
+                    if let Ok((ours, theirs)) = reconcile(node, msgs, peer) {
+                        enqueue(&record(ours).1, peer);
+                        outbox.push(peer, theirs);
+                    }
+                }
+            });
+        }
+
+        node.run(|mut msg| match msg.body["type"].as_str().unwrap() {
+            "broadcast" => {
+                let message = Message {
+                    id: msg.body.get("id").cloned(),
+                    message: msg.body["message"].take(),
+                };
+                let (_, new) = record(vec![message.clone()]);
+                // Stored locally is enough to acknowledge, the outbox taking
+                // care of the neighbours
+                node.reply(
+                    &msg,
+                    json!({
+                        "type": "broadcast_ok"
+                    }),
+                );
+                match mode {
+                    Mode::Flood if !new.is_empty() => {
+                        flood(node, outbox, targets(&msg.src), new[0], message)
+                    }
+                    Mode::Flood => {}
+                    Mode::Batch(_) => enqueue(&new, &msg.src),
+                }
+            }
+            "gossip" => {
+                let batch = serde_json::from_value(msg.body["messages"].take()).unwrap();
+                let (positions, new) = record(batch);
+                // The sender has these already, no need to send them back
+                outbox.forget(&msg.src, &positions);
+                enqueue(&new, &msg.src);
+                node.reply(
+                    &msg,
+                    json!({
+                        "type": "gossip_ok"
+                    }),
+                );
+            }
+            "sync" => {
+                let msgs = msgs.lock();
+                // Keys or digests the peer has but not us, and our messages
+                // it misses
+                let body = match msg.body.get("keys") {
+                    Some(keys) => {
+                        let theirs: BTreeSet<String> =
+                            serde_json::from_value(keys.clone()).unwrap();
+                        let missing = theirs.iter().filter(|k| !msgs.by_key.contains_key(*k));
+                        let ours = msgs.by_key.iter().filter(|(k, _)| !theirs.contains(*k));
+                        json!({
+                            "type": "sync_ok",
+                            "missing": missing.collect::<Vec<_>>(),
+                            "messages": msgs.batch(ours.map(|(_, pos)| pos)),
+                        })
+                    }
+                    None => {
+                        let seed = msg.body["seed"].as_u64().unwrap();
+                        let mut diff: Iblt =
+                            serde_json::from_value(msg.body["iblt"].take()).unwrap();
+                        diff.subtract(&table(&msgs, diff.len(), seed));
+                        match diff.decode() {
+                            Some((missing, ours)) => {
+                                let digests = msgs.digests(seed);
+                                let ours = ours.iter().filter_map(|d| digests.get(d));
+                                json!({
+                                    "type": "sync_ok",
+                                    "missing": missing,
+                                    "messages": msgs.batch(ours.flatten()),
+                                })
+                            }
+                            None => json!({"type": "sync_ok", "decoded": false}),
                         }
                     }
-                })
-            }
-            node.reply(
-                &msg,
-                json!({
-                    "type": "broadcast_ok"
-                }),
-            );
+                };
+                drop(msgs);
+                node.reply(&msg, body);
+            }
+            "read" => {
+                let msgs = msgs.lock();
+                let body = match msg.body["after"].as_u64() {
+                    Some(after) => {
+                        let (messages, cursor) = msgs.since(after as usize);
+                        json!({"type": "read_ok", "messages": messages, "cursor": cursor})
+                    }
+                    None => json!({"type": "read_ok", "messages": msgs.since(0).0}),
+                };
+                drop(msgs);
+                node.reply(&msg, body)
+            }
+            "topology" => {
+                // Our own overlay takes precedence
+                if overlay.is_none() {
+                    let new: Vec<String> =
+                        serde_json::from_value(msg.body["topology"][&node.id].take()).unwrap();
+                    node.storage.save("topology", &new).unwrap();
+                    if let Some(detector) = detector {
+                        detector.watch(&new);
+                    }
+                    *neighbours.write() = new;
+                }
+                node.reply(
+                    &msg,
+                    json!({
+                        "type": "topology_ok"
+                    }),
+                )
+            }
+            ty => unreachable!("msg type {ty}"),
+        });
+    });
+}
+
+/// Messages a peer has yet to acknowledge, retried as one payload
+#[derive(Debug, Default)]
+struct Pending {
+    /// In the order they were queued, the oldest being evicted first
+    msgs: VecDeque<u64>,
+    /// The same messages, to queue each only once
+    queued: BTreeSet<u64>,
+    /// Delay since the last failure, zero while the peer keeps up
+    backoff: Duration,
+    retry_at: Option<Instant>,
+    /// Batches sent and not yet acknowledged
+    in_flight: usize,
+}
+
+/// Bounded per-peer queues of unacknowledged messages
+///
+/// A peer failing to acknowledge is retried with exponential backoff from
+/// the flush interval, everything queued meanwhile joining the retry.
+struct Outbox {
+    base: Duration,
+    limit: usize,
+    peers: Mutex<BTreeMap<String, Pending>>,
+}
+
+impl Outbox {
+    fn new(base: Duration, limit: usize) -> Self {
+        Self {
+            base,
+            limit,
+            peers: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn push(&self, peer: &str, msgs: impl IntoIterator<Item = u64>) {
+        let mut peers = self.peers.lock();
+        let pending = peers.entry(peer.to_owned()).or_default();
+        for msg in msgs {
+            if pending.queued.insert(msg) {
+                pending.msgs.push_back(msg);
+            }
         }
-        "read" => {
-            let messages = msgs.lock().clone();
-            node.reply(
-                &msg,
-                json!({
-                    "type": "read_ok",
-                    "messages": messages
-                }),
-            )
+        while pending.msgs.len() > self.limit {
+            let msg = pending.msgs.pop_front().unwrap();
+            pending.queued.remove(&msg);
         }
-        "topology" => {
-            let new = serde_json::from_value(msg.body["topology"][&node.id].take()).unwrap();
-            *neighbours.write() = new;
-            node.reply(
-                &msg,
-                json!({
-                    "type": "topology_ok"
-                }),
-            )
+    }
+
+    /// Drop messages the peer turned out to have
+    fn forget(&self, peer: &str, msgs: &[u64]) {
+        if let Some(pending) = self.peers.lock().get_mut(peer) {
+            pending.msgs.retain(|m| !msgs.contains(m));
+            pending.queued.retain(|m| !msgs.contains(m));
         }
-        ty => unreachable!("msg type {ty}"),
-    });
+    }
+
+    /// Whether the peer has messages pending, which new ones should join
+    fn is_behind(&self, peer: &str) -> bool {
+        self.peers
+            .lock()
+            .get(peer)
+            .is_some_and(|p| !p.msgs.is_empty() || p.in_flight > 0 || !p.backoff.is_zero())
+    }
+
+    /// Take the batches of the live peers not waiting for a retry
+    ///
+    /// A peer keeping up gets a batch every interval without waiting for the
+    /// last to be acknowledged, one failing a single batch at a time.
+    fn take_due(&self, node: &Node) -> Vec<(String, Vec<u64>)> {
+        let now = Instant::now();
+        let mut peers = self.peers.lock();
+        peers
+            .iter_mut()
+            .filter(|(id, p)| {
+                !p.msgs.is_empty()
+                    && (p.in_flight == 0 || p.backoff.is_zero())
+                    && p.retry_at.is_none_or(|at| at <= now)
+                    && node.is_alive(id)
+            })
+            .map(|(id, p)| {
+                p.in_flight += 1;
+                p.queued.clear();
+                (id.clone(), std::mem::take(&mut p.msgs).into())
+            })
+            .collect()
+    }
+
+    /// Count a message sent outside of the batches
+    fn sent(&self, peer: &str) {
+        self.peers
+            .lock()
+            .entry(peer.to_owned())
+            .or_default()
+            .in_flight += 1;
+    }
+
+    fn acked(&self, peer: &str) {
+        if let Some(pending) = self.peers.lock().get_mut(peer) {
+            pending.in_flight -= 1;
+            pending.backoff = Duration::ZERO;
+            pending.retry_at = None;
+        }
+    }
+
+    /// Queue the batch again, merged with what came meanwhile, for later
+    fn failed(&self, peer: &str, batch: impl IntoIterator<Item = u64>) {
+        self.push(peer, batch);
+        let mut peers = self.peers.lock();
+        let pending = peers.get_mut(peer).unwrap();
+        pending.in_flight -= 1;
+        // An interval past the longest backoff is kept as is
+        pending.backoff = (pending.backoff * 2).min(MAX_BACKOFF).max(self.base);
+        pending.retry_at = Some(Instant::now() + pending.backoff);
+    }
+}
+
+/// Send a new message right away to every target keeping up, leaving it to
+/// the outbox for the others
+fn flood(node: &Node, outbox: &Outbox, targets: BTreeSet<String>, key: u64, message: Message) {
+    let body = &json!({"type": "gossip", "messages": [message]});
+    scope(|s| {
+        for id in targets {
+            if outbox.is_behind(&id) || !node.is_alive(&id) {
+                outbox.push(&id, [key]);
+                continue;
+            }
+            outbox.sent(&id);
+            s.spawn(move || match node.rpc(id.clone(), body.clone()) {
+                Ok(_) => outbox.acked(&id),
+                Err(_) => outbox.failed(&id, [key]),
+            });
+        }
+    })
+}
+
+/// Send each due peer its pending messages as a single `gossip`, which is
+/// acknowledged as a whole, without waiting for the replies
+fn flush<'a>(s: &'a Scope<'a, '_>, node: &'a Node, outbox: &'a Outbox, msgs: &Mutex<Messages>) {
+    for (id, keys) in outbox.take_due(node) {
+        let batch = msgs.lock().batch(&keys);
+        s.spawn(move || {
+            let body = json!({"type": "gossip", "messages": batch});
+            match node.rpc(id.clone(), body) {
+                Ok(_) => outbox.acked(&id),
+                Err(_) => outbox.failed(&id, keys),
+            }
+        });
+    }
+}
+
+fn table(msgs: &Messages, cells: usize, seed: u64) -> Iblt {
+    let mut tmp = Iblt::new(cells);
+    for key in msgs.by_key.keys() {
+        tmp.insert(digest(seed, key));
+    }
+    tmp
+}
+
+/// Exchange with a peer the messages either of us misses, returning those
+/// it sent and the positions of those it asks for
+///
+/// The table sent doubles until the peer decodes our difference, and once
+/// larger than the set, the keys themselves are sent instead.
+fn reconcile(
+    node: &Node,
+    msgs: &Mutex<Messages>,
+    peer: &str,
+) -> Result<(Vec<Message>, Vec<u64>), Err> {
+    let mut cells = SYNC_CELLS;
+    let seed = fastrand::u64(..);
+    loop {
+        let (body, by_key) = {
+            let msgs = msgs.lock();
+            if cells > msgs.order.len() {
+                let keys: Vec<&String> = msgs.by_key.keys().collect();
+                (json!({"type": "sync", "keys": keys}), true)
+            } else {
+                let iblt = table(&msgs, cells, seed);
+                (json!({"type": "sync", "seed": seed, "iblt": iblt}), false)
+            }
+        };
+        let mut res = node.rpc(peer.to_owned(), body)?;
+        if res.body["decoded"] == false {
+            cells *= 2;
+            continue;
+        }
+        let ours = serde_json::from_value(res.body["messages"].take()).unwrap();
+        let missing = res.body["missing"].take();
+        let msgs = msgs.lock();
+        let theirs = if by_key {
+            let keys: Vec<String> = serde_json::from_value(missing).unwrap();
+            keys.iter()
+                .filter_map(|k| msgs.by_key.get(k))
+                .copied()
+                .collect()
+        } else {
+            let digests: Vec<u64> = serde_json::from_value(missing).unwrap();
+            let positions = msgs.digests(seed);
+            let theirs = digests.iter().filter_map(|d| positions.get(d));
+            theirs.flatten().copied().collect()
+        };
+        return Ok((ours, theirs));
+    }
 }
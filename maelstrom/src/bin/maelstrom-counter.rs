@@ -1,54 +1,137 @@
 use std::{
-    collections::BTreeMap,
-    sync::atomic::{AtomicI64, Ordering::SeqCst},
-    thread::{scope, sleep},
-    time::Duration,
+    env,
+    sync::atomic::{AtomicU64, Ordering::SeqCst},
+    thread::scope,
 };
 
-use gossip_glomers::Node;
+use gossip_glomers::{
+    crdt::PNCounter,
+    gossip::{Config, Gossip},
+    Err, Node, KV,
+};
+use parking_lot::Mutex;
 use serde_json::json;
+
+/// Where the counter lives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// A PN-counter in memory, gossiped between the nodes
+    Gossip,
+    /// The contribution of each node under its own key in seq-kv
+    SeqKv,
+}
+
+impl Mode {
+    /// From `$COUNTER_MODE`, `gossip` or `seq-kv`
+    fn from_env() -> Result<Self, String> {
+        match env::var("COUNTER_MODE").as_deref() {
+            Ok("gossip") | Err(_) => Ok(Mode::Gossip),
+            Ok("seq-kv") => Ok(Mode::SeqKv),
+            Ok(mode) => Err(format!("unknown COUNTER_MODE {mode:?}")),
+        }
+    }
+}
+
+/// Attempts of an add whose cas keeps failing, before giving up
+const MAX_CAS_TRIES: usize = 10;
+
+/// Counter stored in seq-kv, each node adding to its own key only
+struct SeqKvCounter<'a> {
+    node: &'a Node,
+    /// Last value of our key, None until read
+    ours: Mutex<Option<i64>>,
+    /// Distinct values of the writes forcing fresh reads
+    fresh: AtomicU64,
+}
+
+impl SeqKvCounter<'_> {
+    fn key(id: &str) -> String {
+        format!("counter-{id}")
+    }
+
+    fn get(&self, id: &str) -> Result<i64, Err> {
+        match self.node.read(KV::Seq, &Self::key(id)) {
+            Err(Err::KeyDoesNotExist) => Ok(0),
+            res => res,
+        }
+    }
+
+    /// Order our next reads after every write acknowledged so far
+    ///
+    /// seq-kv may serve stale reads, but none older than our own last write,
+    /// which a unique value keeps from being a no-op.
+    fn refresh(&self) -> Result<(), Err> {
+        let fresh = format!("{}-{}", self.node.id, self.fresh.fetch_add(1, SeqCst));
+        let key = format!("fresh-{}", self.node.id);
+        self.node.write(KV::Seq, &key, fresh)
+    }
+
+    /// Read-modify-write of our key, a cas from a stale value failing and
+    /// refreshing it
+    fn add(&self, delta: i64) -> Result<(), Err> {
+        let mut ours = self.ours.lock();
+        for _ in 0..MAX_CAS_TRIES {
+            let from = match *ours {
+                Some(from) => from,
+                None => {
+                    self.refresh()?;
+                    *ours.insert(self.get(&self.node.id)?)
+                }
+            };
+            let key = Self::key(&self.node.id);
+            match self.node.cap(KV::Seq, &key, from, from + delta, from == 0) {
+                Ok(()) => {
+                    *ours = Some(from + delta);
+                    return Ok(());
+                }
+                Err(Err::PreconditionFailed | Err::KeyAlreadyExists | Err::KeyDoesNotExist) => {
+                    *ours = None;
+                }
+                // Retrying could apply the delta twice
+                Err(e) => {
+                    *ours = None;
+                    return Err(e);
+                }
+            }
+        }
+        // Nothing was applied
+        Err(Err::TemporarilyUnavailable)
+    }
+
+    /// Sum of every contribution, including every add acknowledged before
+    /// this read began
+    fn value(&self) -> Result<i64, Err> {
+        self.refresh()?;
+        self.node.node_ids.iter().map(|id| self.get(id)).sum()
+    }
+}
+
 fn main() {
     let node = &Node::new();
-    let state: BTreeMap<String, AtomicI64> = node
-        .node_ids
-        .iter()
-        .cloned()
-        .map(|s| (s, AtomicI64::new(0)))
-        .collect();
+    let mode = Mode::from_env().unwrap();
+    let gossip = &Gossip::<PNCounter>::new(Config::default());
+    let seq_kv = &SeqKvCounter {
+        node,
+        ours: Mutex::new(None),
+        fresh: AtomicU64::new(0),
+    };
 
     scope(|s| {
-        // Broadcast every 1 second
-        s.spawn(|| loop {
-            sleep(Duration::from_secs(1));
-            let current = state[&node.id].load(SeqCst);
-            for id in node.other_ids() {
-                let node = &node;
-                s.spawn(move || {
-                    node.rpc(
-                        id.clone(),
-                        json!({
-                            "type": "broadcast",
-                            "counter": current
-                        }),
-                    )
-                    .ok();
-                });
-            }
-        });
+        if mode == Mode::Gossip {
+            gossip.start(s, node);
+        }
 
         node.run(|msg| match msg.body["type"].as_str().unwrap() {
-            "broadcast" => {
-                let counter = msg.body["counter"].as_i64().unwrap();
-                state[&msg.src].store(counter, SeqCst);
-                node.reply(
-                    &msg,
-                    json!({
-                        "type": "broadcast_ok"
-                    }),
-                );
-            }
+            "gossip" => gossip.on_gossip(node, &msg),
+            "gossip_sync" => gossip.on_sync(node, &msg),
             "read" => {
-                let sum = state.values().map(|i| i.load(SeqCst)).sum::<i64>();
+                let sum = match mode {
+                    Mode::Gossip => gossip.state().value(),
+                    Mode::SeqKv => match seq_kv.value() {
+                        Ok(sum) => sum,
+                        Err(e) => return node.reply(&msg, e.msg()),
+                    },
+                };
                 node.reply(
                     &msg,
                     json!({
@@ -58,7 +141,15 @@ fn main() {
                 )
             }
             "add" => {
-                state[&node.id].fetch_add(msg.body["delta"].as_i64().unwrap(), SeqCst);
+                let delta = msg.body["delta"].as_i64().unwrap();
+                match mode {
+                    Mode::Gossip => gossip.state().add(&node.id, delta),
+                    Mode::SeqKv => {
+                        if let Err(e) = seq_kv.add(delta) {
+                            return node.reply(&msg, e.msg());
+                        }
+                    }
+                }
                 node.reply(
                     &msg,
                     json!({
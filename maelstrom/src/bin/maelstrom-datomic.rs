@@ -156,7 +156,7 @@ fn main() {
                     .map(|m| serde_json::from_value(m.body["txn"].take()).unwrap())
                     .collect();
                 let result = actor.run(node, txns);
-                for (msg, result) in msgs.drain(..).zip(result.into_iter()) {
+                for (msg, result) in msgs.drain(..).zip(result) {
                     node.reply(
                         &msg,
                         json!({
@@ -1,15 +1,50 @@
 use std::{collections::BTreeMap, thread::scope};
 
-use gossip_glomers::{Node, KV};
+use gossip_glomers::{sharding::Ring, Err, Node, KV};
 use parking_lot::Mutex;
-use serde_json::json;
+use serde_json::{json, Value};
+
+/// Handle our own keys with `local` and send the others to their owners
+/// with `remote`, returning every reply body
+fn fan_out(
+    node: &Node,
+    ring: &Ring,
+    keys: impl IntoIterator<Item = String>,
+    local: impl Fn(&[String]) -> Value,
+    remote: impl Fn(&[String]) -> Value + Sync,
+) -> Result<Vec<Value>, Err> {
+    let mut owners: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for key in keys {
+        let owner = ring.owner(&key).unwrap_or(&node.id);
+        owners.entry(owner.clone()).or_default().push(key);
+    }
+    let ours = owners.remove(&node.id).unwrap_or_default();
+    let remote = &remote;
+    let mut replies: Vec<Value> = scope(|s| {
+        let handles: Vec<_> = owners
+            .into_iter()
+            .map(|(owner, keys)| s.spawn(move || node.rpc(owner, remote(&keys))))
+            .collect();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap().map(|res| res.body))
+            .collect::<Result<_, _>>()
+    })?;
+    replies.push(local(&ours));
+    Ok(replies)
+}
 
 fn main() {
     let commit: Mutex<BTreeMap<String, u64>> = Mutex::new(BTreeMap::new());
     let node = &Node::new();
+    // Funnel the sends of a key through its owner to limit cas contention
+    let ring = Ring::new(&node.node_ids, 64);
     node.run(|mut msg| match msg.body["type"].as_str().unwrap() {
         "send" => {
             let key = msg.body["key"].as_str().unwrap().to_owned();
+            if ring.route(node, &key, &msg) {
+                return;
+            }
             loop {
                 let result = node.read(KV::Lin, &key);
                 let prev: Option<u64> = result.ok();
@@ -23,6 +58,8 @@ fn main() {
                 }
             }
         }
+        // Messages are read straight from lin-kv, the same from any node, so
+        // polls need no routing
         "poll" => {
             let offsets: BTreeMap<String, u64> =
                 serde_json::from_value(msg.body["offsets"].take()).unwrap();
@@ -45,37 +82,60 @@ fn main() {
             });
             node.reply(&msg, json!({"type": "poll_ok", "msgs": msgs}));
         }
+        // Committed offsets live on the owner of their key only
         "commit_offsets" => {
             let offsets: BTreeMap<String, u64> =
                 serde_json::from_value(msg.body["offsets"].take()).unwrap();
-            {
+            let local = |keys: &[String]| {
                 let mut lock = commit.lock();
-                for (k, offset) in offsets {
-                    *lock.entry(k).or_default() = offset;
+                for k in keys {
+                    lock.insert(k.clone(), offsets[k]);
                 }
+                Value::Null
+            };
+            let remote = |keys: &[String]| {
+                let sub: BTreeMap<&String, u64> = keys.iter().map(|k| (k, offsets[k])).collect();
+                json!({"type": "commit_offsets", "offsets": sub})
+            };
+            match fan_out(node, &ring, offsets.keys().cloned(), local, remote) {
+                Ok(_) => node.reply(
+                    &msg,
+                    json!({
+                        "type": "commit_offsets_ok"
+                    }),
+                ),
+                Err(e) => node.reply(&msg, e.msg()),
             }
-            node.reply(
-                &msg,
-                json!({
-                    "type": "commit_offsets_ok"
-                }),
-            );
         }
         "list_committed_offsets" => {
             let keys: Vec<String> = serde_json::from_value(msg.body["keys"].take()).unwrap();
-            let offsets: BTreeMap<&String, u64> = {
+            let local = |keys: &[String]| {
                 let lock = commit.lock();
-                keys.iter()
-                    .map(|s| (s, *lock.get(s).unwrap_or(&0)))
-                    .collect()
+                let offsets: BTreeMap<&String, u64> = keys
+                    .iter()
+                    .map(|k| (k, *lock.get(k).unwrap_or(&0)))
+                    .collect();
+                json!({ "offsets": offsets })
             };
-            node.reply(
-                &msg,
-                json!({
-                    "type": "list_committed_offsets_ok",
-                    "offsets": offsets
-                }),
-            );
+            let remote = |keys: &[String]| json!({"type": "list_committed_offsets", "keys": keys});
+            match fan_out(node, &ring, keys, local, remote) {
+                Ok(replies) => {
+                    let mut offsets = serde_json::Map::new();
+                    for mut reply in replies {
+                        if let Value::Object(part) = reply["offsets"].take() {
+                            offsets.extend(part);
+                        }
+                    }
+                    node.reply(
+                        &msg,
+                        json!({
+                            "type": "list_committed_offsets_ok",
+                            "offsets": offsets
+                        }),
+                    );
+                }
+                Err(e) => node.reply(&msg, e.msg()),
+            }
         }
         ty => unreachable!("msg type {ty}"),
     });
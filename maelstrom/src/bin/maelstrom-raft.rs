@@ -6,8 +6,12 @@ use std::{
 };
 
 use gossip_glomers::Node;
-use gossip_glomers::{Err, Msg};
+use gossip_glomers::{
+    storage::{self, Storage},
+    Err, Msg,
+};
 use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
 struct StateMachine {
@@ -68,13 +72,26 @@ pub fn median(mut items: Vec<u64>) -> u64 {
     items[items.len() - majority(items.len())]
 }
 
+/// Durable change to the log: truncate it to `len`, then append `entries`
+#[derive(Serialize, Deserialize)]
+struct Splice {
+    len: u64,
+    entries: Vec<(u64, Msg)>,
+}
+
 struct Log {
     entries: Vec<(u64, Msg)>,
+    wal: storage::Log<Splice>,
+    /// Entries recovered from storage and still in place, whose requests
+    /// were made to a previous incarnation of the node
+    recovered: u64,
 }
 
 impl Log {
-    pub fn new() -> Self {
-        Self {
+    /// Log recovered from the node storage
+    pub fn open(storage: &Storage) -> Self {
+        let (wal, splices) = storage.log("raft-log").unwrap();
+        let mut tmp = Self {
             entries: vec![(
                 0,
                 Msg {
@@ -83,11 +100,47 @@ impl Log {
                     body: Value::Null,
                 },
             )],
+            wal,
+            recovered: 0,
+        };
+        for Splice { len, entries } in splices {
+            tmp.entries.truncate(len as usize);
+            tmp.entries.extend(entries);
         }
+        tmp.recovered = tmp.size();
+        tmp
     }
 
-    pub fn append(&mut self, entries: impl IntoIterator<Item = (u64, Msg)>) {
-        self.entries.extend(entries);
+    pub fn append(&mut self, entries: Vec<(u64, Msg)>) {
+        self.splice(self.size(), entries);
+    }
+
+    /// Replace the entries from `len` on, synced to disk before returning
+    pub fn splice(&mut self, len: u64, entries: Vec<(u64, Msg)>) {
+        let splice = Splice { len, entries };
+        self.wal.append(&splice).unwrap();
+        self.recovered = self.recovered.min(len);
+        self.entries.truncate(len as usize);
+        self.entries.extend(splice.entries);
+    }
+
+    /// Add the leader's entries following the first `len`, truncating ours
+    /// only from the first conflicting one so stale requests lose nothing
+    pub fn reconcile(&mut self, mut len: u64, entries: Vec<(u64, Msg)>) {
+        let mut entries = entries.into_iter().peekable();
+        while let Some((term, _)) = entries.peek() {
+            match self.get(len + 1) {
+                Some((ours, _)) if ours == term => {
+                    len += 1;
+                    entries.next();
+                }
+                _ => break,
+            }
+        }
+        let rest: Vec<_> = entries.collect();
+        if !rest.is_empty() {
+            self.splice(len, rest);
+        }
     }
 
     pub fn last(&self) -> &(u64, Msg) {
@@ -98,17 +151,18 @@ impl Log {
         self.entries.len() as u64
     }
 
-    pub fn from_index(&self, idx: u64) -> &[(u64, Msg)] {
+    pub fn since(&self, idx: u64) -> &[(u64, Msg)] {
         &self.entries[idx as usize - 1..]
     }
 
-    pub fn truncate(&mut self, len: u64) {
-        self.entries.drain(len as usize..);
-    }
-
     pub fn get(&self, idx: u64) -> Option<&(u64, Msg)> {
         self.entries.get(idx as usize - 1)
     }
+
+    /// Whether the entry was recovered from storage on start
+    pub fn is_recovered(&self, idx: u64) -> bool {
+        idx <= self.recovered
+    }
 }
 
 impl Index<u64> for Log {
@@ -143,27 +197,39 @@ struct Raft {
     match_index: BTreeMap<String, u64>,
     commit_index: u64,
     leader: Option<String>,
+    storage: Storage,
 }
 
 impl Raft {
-    pub fn new() -> Self {
+    /// Raft resuming from the term, vote and log in the node storage
+    pub fn new(node: &Node) -> Self {
+        let storage = node.storage.clone();
+        let (term, voted_for) = storage.load("raft-vote").unwrap().unwrap_or((0, None));
         Self {
             election_deadline: Instant::now(),
             step_down_deadline: Instant::now(),
             state: State::Follower,
             commit_index: 0,
-            term: 0,
-            log: Log::new(),
-            voted_for: None,
+            term,
+            log: Log::open(&storage),
+            voted_for,
             machine: StateMachine::new(),
             last_replication: Instant::now(),
             next_index: BTreeMap::new(),
             match_index: BTreeMap::new(),
             last_applied: 1,
             leader: None,
+            storage,
         }
     }
 
+    /// Sync the term and vote to disk, before any message depending on them
+    pub fn persist(&self) {
+        self.storage
+            .save("raft-vote", &(self.term, &self.voted_for))
+            .unwrap();
+    }
+
     pub fn become_candidate<'a, 'b>(
         &mut self,
         s: &'b Scope<'b, 'a>,
@@ -173,6 +239,7 @@ impl Raft {
         self.state = State::Candidate;
         self.advance_term(self.term + 1);
         self.voted_for = Some(node.id.to_owned());
+        self.persist();
         self.leader = None;
         self.reset_election_deadline();
         self.request_vote(s, node, raft);
@@ -208,6 +275,7 @@ impl Raft {
                 self.term
             );
             self.advance_term(remote_term);
+            self.persist();
             self.become_follower();
         }
     }
@@ -236,6 +304,7 @@ impl Raft {
             eprintln!("Granting vote to {candidate_id}");
             granted = true;
             self.voted_for = Some(candidate_id.to_owned());
+            self.persist();
             self.reset_election_deadline();
         }
 
@@ -298,7 +367,7 @@ impl Raft {
         if self.state == State::Leader && MIN_REPLICATION_INTERVAL < elapsed_time {
             for id in node.other_ids() {
                 let ni = self.next_index[id];
-                let entries = self.log.from_index(ni);
+                let entries = self.log.since(ni);
                 if !entries.is_empty() || HEARTBEAT_INTERVAL < elapsed_time {
                     eprintln!("Replicating {ni}+ to {id} for {}", self.term);
                     replicated = true;
@@ -384,7 +453,9 @@ impl Raft {
             self.last_applied += 1;
             let msg = &self.log[self.last_applied].1;
             let res = self.machine.apply(&msg.body);
-            if self.state == State::Leader {
+            // Entries replayed after a restart only rebuild the state machine,
+            // their requesters having long given up or been answered already
+            if self.state == State::Leader && !self.log.is_recovered(self.last_applied) {
                 node.reply(msg, res);
             }
         }
@@ -395,7 +466,7 @@ impl Raft {
             let n = median(
                 self.match_index
                     .values()
-                    .map(|i| *i)
+                    .copied()
                     .chain([self.log.size()])
                     .collect(),
             );
@@ -409,20 +480,26 @@ impl Raft {
 }
 
 fn main() {
-    let raft = Mutex::new(Raft::new());
     let node = &Node::new();
+    let raft = Mutex::new(Raft::new(node));
     scope(|s| {
-        s.spawn(|| loop {
-            sleep(Duration::from_millis(fastrand::u64(100..200)));
-            raft.lock().tick_deadline(s, node, &raft);
+        s.spawn(|| {
+            while !node.is_closed() {
+                sleep(Duration::from_millis(fastrand::u64(100..200)));
+                raft.lock().tick_deadline(s, node, &raft);
+            }
         });
-        s.spawn(|| loop {
-            sleep(Duration::from_millis(100));
-            raft.lock().tick_step_down();
+        s.spawn(|| {
+            while !node.is_closed() {
+                sleep(Duration::from_millis(100));
+                raft.lock().tick_step_down();
+            }
         });
-        s.spawn(|| loop {
-            sleep(MIN_REPLICATION_INTERVAL);
-            raft.lock().replicate_log(s, node, &raft)
+        s.spawn(|| {
+            while !node.is_closed() {
+                sleep(MIN_REPLICATION_INTERVAL);
+                raft.lock().replicate_log(s, node, &raft)
+            }
         });
         node.run(|mut msg| match msg.body["type"].as_str().unwrap() {
             "read" | "write" | "cas" => {
@@ -430,7 +507,7 @@ fn main() {
                     let mut r = raft.lock();
                     if r.state == State::Leader {
                         let term = r.term;
-                        r.log.append([(term, msg.clone())]);
+                        r.log.append(vec![(term, msg.clone())]);
                         None
                     } else {
                         Some(r.leader.clone())
@@ -438,14 +515,13 @@ fn main() {
                 };
                 if let Some(forward) = forward {
                     let res = if let Some(leader) = forward {
-                        match node.rpc(leader, msg.body.clone()) {
-                            Ok(res) => res.body,
-                            Err(e) => e.msg(),
-                        }
+                        node.forward(leader, &msg)
                     } else {
-                        Err::TemporarilyUnavailable.msg()
+                        Err(Err::TemporarilyUnavailable)
                     };
-                    node.reply(&msg, res);
+                    if let Err(e) = res {
+                        node.reply(&msg, e.msg());
+                    }
                 }
             }
             "request_vote" => {
@@ -453,9 +529,10 @@ fn main() {
                 node.reply(&msg, result);
             }
             "append_entries" => {
-                let mut success = true;
+                let mut success = false;
                 let term = msg.body["term"].as_u64().unwrap();
                 let prev_log_index = msg.body["prev_log_index"].as_u64().unwrap();
+                let prev_log_term = msg.body["prev_log_term"].as_u64().unwrap();
                 let commit_index = msg.body["leader_commit"].as_u64().unwrap();
                 let leader_id = msg.body["leader_id"].as_str().unwrap().to_owned();
                 let entries: Vec<(u64, Msg)> =
@@ -467,9 +544,8 @@ fn main() {
                         lock.leader = Some(leader_id);
                         lock.reset_election_deadline();
                         if let Some(prev) = &lock.log.get(prev_log_index) {
-                            if prev.0 == lock.term {
-                                lock.log.truncate(prev_log_index);
-                                lock.log.append(entries);
+                            if prev.0 == prev_log_term {
+                                lock.log.reconcile(prev_log_index, entries);
                                 if lock.commit_index < commit_index {
                                     lock.commit_index = lock.log.size().min(commit_index);
                                     lock.advance_state_machine(node);
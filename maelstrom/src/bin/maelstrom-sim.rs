@@ -0,0 +1,460 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::exit,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use gossip_glomers::{
+    checker::{broadcast, elle, kafka, linearizable, pn_counter, unique_ids},
+    cluster::Processes,
+    edn::Edn,
+    generator::{self, Workload},
+    history::{History, Type},
+    nemesis::{self, Kind, Plan},
+    net::{self, Dist},
+    shrink::{self, Case, Repro},
+    topology::Topology,
+    KV,
+};
+use serde_json::{json, Value};
+
+const USAGE: &str = "usage: maelstrom-sim test -w WORKLOAD --bin BIN [--node-count N]
+    [--concurrency N|Nn] [--rate R] [--time-limit S] [--latency MS]
+    [--latency-dist constant|uniform|exponential] [--nemesis KIND,...]
+    [--nemesis-interval S] [--topology grid|line|total|tree2|tree3|tree4]
+    [--key-count N] [--max-txn-length N] [--max-writes-per-key N]
+    [--consistency-models MODEL] [--availability total|FRACTION]
+    [--test-count N] [--seed N] [--store DIR] [--log-stderr]
+    [--replay CASE.json|REPRO.json] [--shrink]
+       maelstrom-sim check -w WORKLOAD [--consistency-models MODEL]
+    [--availability total|FRACTION] STORE_DIR|HISTORY.edn|HISTORY.json";
+
+struct Opts {
+    /// `test` to run a binary, `check` to check a recorded history
+    command: String,
+    /// History or store directory to check
+    path: Option<PathBuf>,
+    /// Workload name, for the results directory
+    name: String,
+    bin: PathBuf,
+    node_count: usize,
+    /// Concurrency, as a multiple of the node count if ending with `n`
+    concurrency: String,
+    generator: generator::Config,
+    net: net::Config,
+    nemesis: nemesis::Config,
+    model: elle::Model,
+    availability: Option<f64>,
+    test_count: usize,
+    store: PathBuf,
+    log_stderr: bool,
+    replay: Option<PathBuf>,
+    shrink: bool,
+}
+
+fn fail(msg: impl AsRef<str>) -> ! {
+    eprintln!("{}\n{USAGE}", msg.as_ref());
+    exit(2)
+}
+
+fn parse<T: std::str::FromStr>(flag: &str, value: &str) -> T {
+    value
+        .parse()
+        .unwrap_or_else(|_| fail(format!("invalid value {value:?} for {flag}")))
+}
+
+fn secs(flag: &str, value: &str) -> Duration {
+    Duration::from_secs_f64(parse(flag, value))
+}
+
+fn opts(mut args: impl Iterator<Item = String>) -> Opts {
+    let command = args.next().unwrap_or_default();
+    if !matches!(command.as_str(), "test" | "check") {
+        fail("expected the test or check command");
+    }
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos() as u64;
+    let mut opts = Opts {
+        command,
+        path: None,
+        name: String::new(),
+        bin: PathBuf::new(),
+        node_count: 1,
+        concurrency: "1n".to_owned(),
+        generator: generator::Config {
+            seed,
+            ..Default::default()
+        },
+        net: net::Config {
+            seed,
+            trace: true,
+            ..Default::default()
+        },
+        nemesis: nemesis::Config {
+            seed,
+            ..Default::default()
+        },
+        model: elle::Model::Serializable,
+        availability: None,
+        test_count: 1,
+        store: PathBuf::from("store"),
+        log_stderr: false,
+        replay: None,
+        shrink: false,
+    };
+    let mut workload = None;
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--log-stderr" => {
+                opts.log_stderr = true;
+                continue;
+            }
+            "--shrink" => {
+                opts.shrink = true;
+                continue;
+            }
+            path if !path.starts_with('-') && opts.path.is_none() => {
+                opts.path = Some(PathBuf::from(path));
+                continue;
+            }
+            _ => {}
+        }
+        let value = args
+            .next()
+            .unwrap_or_else(|| fail(format!("missing value for {flag}")));
+        let v = value.as_str();
+        match flag.as_str() {
+            "-w" | "--workload" => {
+                workload = Some(parse::<Workload>(&flag, v));
+                opts.name = value;
+            }
+            "--bin" => opts.bin = PathBuf::from(v),
+            "--node-count" => opts.node_count = parse(&flag, v),
+            "--concurrency" => opts.concurrency = value,
+            "--rate" => opts.generator.rate = parse(&flag, v),
+            "--time-limit" => opts.generator.time_limit = secs(&flag, v),
+            "--key-count" => opts.generator.key_count = parse(&flag, v),
+            "--max-txn-length" => opts.generator.max_txn_length = parse(&flag, v),
+            "--max-writes-per-key" => opts.generator.max_writes_per_key = parse(&flag, v),
+            "--latency" => opts.net.latency = Duration::from_millis(parse(&flag, v)),
+            "--latency-dist" => opts.net.dist = parse::<Dist>(&flag, v),
+            "--nemesis" => {
+                opts.nemesis.kinds = v.split(',').map(|k| parse::<Kind>(&flag, k)).collect()
+            }
+            "--nemesis-interval" => opts.nemesis.interval = secs(&flag, v),
+            "--topology" => {
+                opts.generator.topology = match v {
+                    "grid" => Topology::grid,
+                    "line" => |ids| Topology::tree(ids, 1),
+                    "total" => |ids| {
+                        let mut tmp = Topology::empty(ids);
+                        for (a, b) in ids.iter().flat_map(|a| ids.iter().map(move |b| (a, b))) {
+                            tmp.link(a, b);
+                        }
+                        tmp
+                    },
+                    "tree2" => |ids| Topology::tree(ids, 2),
+                    "tree3" => |ids| Topology::tree(ids, 3),
+                    "tree4" => |ids| Topology::tree(ids, 4),
+                    _ => fail(format!("unknown topology {v}")),
+                }
+            }
+            "--consistency-models" => {
+                let first = v.split(',').next().unwrap_or_default();
+                opts.model = parse(&flag, first);
+            }
+            "--availability" => {
+                opts.availability = Some(match v {
+                    "total" => 1.,
+                    _ => parse(&flag, v),
+                })
+            }
+            "--test-count" => opts.test_count = parse(&flag, v),
+            "--seed" => {
+                let seed = parse(&flag, v);
+                (opts.generator.seed, opts.net.seed, opts.nemesis.seed) = (seed, seed, seed);
+            }
+            "--store" => opts.store = PathBuf::from(v),
+            "--replay" => opts.replay = Some(PathBuf::from(v)),
+            _ => fail(format!("unknown option {flag}")),
+        }
+    }
+    opts.generator.workload = workload.unwrap_or_else(|| fail("missing -w"));
+    match opts.command.as_str() {
+        "test" if opts.bin.as_os_str().is_empty() => fail("missing --bin"),
+        "check" if opts.path.is_none() => fail("missing history to check"),
+        _ => {}
+    }
+    opts.generator.concurrency = match opts.concurrency.strip_suffix('n') {
+        Some(k) => parse::<usize>("--concurrency", k) * opts.node_count,
+        None => parse("--concurrency", &opts.concurrency),
+    };
+    opts.nemesis.time_limit = opts.generator.time_limit;
+    opts
+}
+
+/// Message counts as maelstrom's `:net` results, over all messages and those
+/// between nodes, per client operation
+fn net_stats(history: &History, trace: &[net::Event]) -> Value {
+    let ops = history
+        .ops
+        .iter()
+        .filter(|op| op.ty == Type::Invoke)
+        .count();
+    let is_node = |id: &str| {
+        id.strip_prefix('n')
+            .is_some_and(|n| n.parse::<u64>().is_ok())
+    };
+    let servers = trace
+        .iter()
+        .filter(|e| is_node(&e.msg.src) && is_node(&e.msg.dest))
+        .count();
+    let stats = |count: usize| {
+        json!({
+            "send-count": count,
+            "msgs-per-op": count as f64 / ops.max(1) as f64,
+        })
+    };
+    json!({"all": stats(trace.len()), "servers": stats(servers)})
+}
+
+/// Check the history with the workload checker and the availability
+/// requirement, returning the validity and the results
+fn check(opts: &Opts, history: &History) -> (bool, Value) {
+    let workload = match opts.generator.workload {
+        Workload::Echo => {
+            let pairs = history.pairs();
+            let wrong: Vec<_> = pairs
+                .iter()
+                .filter(|p| p.ty() == Type::Ok && p.value() != &p.invoke.value)
+                .map(|p| p.invoke.index)
+                .collect();
+            json!({"valid": wrong.is_empty(), "wrong": wrong})
+        }
+        Workload::UniqueIds => json!(unique_ids::check(history)),
+        Workload::Broadcast => json!(broadcast::check(history)),
+        Workload::PnCounter => json!(pn_counter::check(history)),
+        Workload::Kafka => json!(kafka::check(history)),
+        Workload::LinKv => json!(linearizable::check(history)),
+        Workload::TxnRwRegister => {
+            json!(elle::check(history, elle::Workload::RwRegister, opts.model))
+        }
+        Workload::TxnListAppend => {
+            json!(elle::check(history, elle::Workload::ListAppend, opts.model))
+        }
+    };
+    let pairs = history.pairs();
+    let ok = pairs.iter().filter(|p| p.ty() == Type::Ok).count();
+    let fraction = ok as f64 / pairs.len().max(1) as f64;
+    let available = opts.availability.map(|min| fraction >= min).unwrap_or(true);
+    let valid = workload["valid"] == true && available;
+    let results = json!({
+        "valid": valid,
+        "workload": workload,
+        "availability": {"valid": available, "ok-fraction": fraction},
+        "stats": {"count": pairs.len(), "ok-count": ok},
+    });
+    (valid, results)
+}
+
+/// History indices of the operations behind a checker failure, empty if the
+/// checker does not point at any
+fn blamed(opts: &Opts, history: &History) -> Vec<usize> {
+    let elle = |workload| {
+        let report = elle::check(history, workload, opts.model);
+        let txns = report.anomalies.iter().flat_map(|a| &a.txns);
+        txns.map(|op| op.index).collect()
+    };
+    match opts.generator.workload {
+        Workload::LinKv => {
+            let report = linearizable::check(history);
+            let ops = report.failures.iter().flat_map(|f| &f.ops);
+            ops.map(|op| op.index).collect()
+        }
+        Workload::TxnRwRegister => elle(elle::Workload::RwRegister),
+        Workload::TxnListAppend => elle(elle::Workload::ListAppend),
+        Workload::Kafka => {
+            let report = kafka::check(history);
+            report.nonmonotonic.iter().map(|op| op.index).collect()
+        }
+        Workload::UniqueIds => {
+            let report = unique_ids::check(history);
+            let ops = history.ops.iter().filter(|op| {
+                op.ty == Type::Ok && report.duplicated.contains_key(&op.value.to_string())
+            });
+            ops.map(|op| op.index).collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Run a case on fresh node processes, logging to `dir` unless `--log-stderr`
+fn run(opts: &Opts, case: &Case, dir: &Path) -> (History, Vec<net::Event>) {
+    let logs = (!opts.log_stderr).then(|| dir.join("node-logs"));
+    if let Some(logs) = &logs {
+        fs::create_dir_all(logs).unwrap();
+    }
+    // Every run starts from empty state, kept across restarts within it
+    let state = dir.join("state");
+    fs::remove_dir_all(&state).ok();
+    let cluster = Processes::new(
+        opts.net.clone(),
+        opts.node_count,
+        opts.bin.clone(),
+        logs,
+        Some(state),
+    )
+    .unwrap_or_else(|e| fail(format!("cannot start {}: {e}", opts.bin.display())));
+    for kv in [KV::Lin, KV::Seq, KV::LWW] {
+        cluster.net.serve(kv);
+    }
+    let history = shrink::replay(
+        &cluster.net,
+        &cluster.nodes,
+        &cluster,
+        &opts.generator,
+        case,
+    );
+    let trace = cluster.net.trace();
+    (history, trace)
+}
+
+fn write(path: PathBuf, value: &impl serde::Serialize) {
+    fs::write(&path, serde_json::to_vec_pretty(value).unwrap())
+        .unwrap_or_else(|e| fail(format!("cannot write {}: {e}", path.display())));
+}
+
+/// Check a history recorded by maelstrom or by a previous test, comparing
+/// with maelstrom's verdict in the `results.edn` next to it if any
+fn check_recorded(opts: &Opts) -> bool {
+    let mut path = opts.path.clone().unwrap();
+    if path.is_dir() {
+        let edn = path.join("history.edn");
+        path = if edn.exists() {
+            edn
+        } else {
+            path.join("history.json")
+        };
+    }
+    let text = fs::read_to_string(&path)
+        .unwrap_or_else(|e| fail(format!("cannot read {}: {e}", path.display())));
+    let history = match path.extension().and_then(|e| e.to_str()) {
+        Some("edn") => History::from_edn(&text),
+        _ => serde_json::from_str(&text).map_err(|e| e.to_string()),
+    }
+    .unwrap_or_else(|e| fail(format!("invalid history {}: {e}", path.display())));
+    let (valid, results) = check(opts, &history);
+    println!("{}", serde_json::to_string_pretty(&results).unwrap());
+
+    let theirs = path.with_file_name("results.edn");
+    let Ok(text) = fs::read_to_string(&theirs) else {
+        return valid;
+    };
+    let verdict = text
+        .parse::<Edn>()
+        .ok()
+        .and_then(|results| results.get("valid?").cloned());
+    match verdict {
+        Some(Edn::Bool(v)) if v != valid => {
+            println!("Checkers disagree: maelstrom found the history valid? {v}");
+            false
+        }
+        Some(v) => {
+            println!("Maelstrom agrees, valid? {v}");
+            valid
+        }
+        None => valid,
+    }
+}
+
+fn main() {
+    let mut opts = opts(env::args().skip(1));
+    if opts.command == "check" {
+        exit(if check_recorded(&opts) { 0 } else { 1 })
+    }
+    let mut all_valid = true;
+    for test in 0..opts.test_count {
+        let seed = opts.generator.seed.wrapping_add(test as u64);
+        (opts.generator.seed, opts.net.seed, opts.nemesis.seed) = (seed, seed, seed);
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let dir = opts
+            .store
+            .join(&opts.name)
+            .join(now.as_millis().to_string());
+        fs::create_dir_all(&dir).unwrap();
+        let latest = opts.store.join("latest");
+        fs::remove_file(&latest).ok();
+        std::os::unix::fs::symlink(dir.canonicalize().unwrap(), latest).ok();
+
+        let case = match &opts.replay {
+            Some(path) => {
+                let json = fs::read(path)
+                    .unwrap_or_else(|e| fail(format!("cannot read {}: {e}", path.display())));
+                // Either a case.json or the repro.json written by --shrink
+                serde_json::from_slice::<Repro>(&json)
+                    .map(|repro| repro.case)
+                    .or_else(|_| serde_json::from_slice(&json))
+                    .unwrap_or_else(|e| fail(format!("invalid case {}: {e}", path.display())))
+            }
+            None => {
+                let nodes: Vec<String> = (0..opts.node_count).map(|i| format!("n{i}")).collect();
+                Case {
+                    plan: Plan::generate(&opts.nemesis, &nodes),
+                    requests: generator::script(&opts.generator),
+                }
+            }
+        };
+        eprintln!(
+            "test {} seed {seed}, results in {}",
+            test + 1,
+            dir.display()
+        );
+        let (history, trace) = run(&opts, &case, &dir);
+        let (valid, mut results) = check(&opts, &history);
+        results["net"] = net_stats(&history, &trace);
+        write(dir.join("case.json"), &case);
+        write(dir.join("history.json"), &history);
+        fs::write(dir.join("history.edn"), history.to_edn()).unwrap();
+        write(dir.join("messages.json"), &trace);
+        write(dir.join("results.json"), &results);
+
+        if !valid && opts.shrink {
+            eprintln!(
+                "shrinking {} faults and {} requests",
+                case.plan.steps.len() / 2,
+                case.requests.len()
+            );
+            let min = shrink::shrink(&case, |case| !check(&opts, &run(&opts, case, &dir).0).0);
+            let (history, trace) = run(&opts, &min, &dir);
+            // The whole run if the checker blames no operation in particular
+            let (from, to) =
+                shrink::span(&history, blamed(&opts, &history)).unwrap_or((0, u64::MAX));
+            let repro = Repro {
+                case: min,
+                trace: shrink::window(&trace, from, to),
+            };
+            write(dir.join("repro.json"), &repro);
+            eprintln!(
+                "shrunk to {} faults and {} requests, replay with --replay {}",
+                repro.case.plan.steps.len() / 2,
+                repro.case.requests.len(),
+                dir.join("repro.json").display()
+            );
+        }
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&results["workload"]).unwrap()
+        );
+        println!("{}", serde_json::to_string_pretty(&results["net"]).unwrap());
+        if valid {
+            println!("Everything looks good!");
+        } else {
+            println!("Analysis invalid!");
+            all_valid = false;
+        }
+    }
+    exit(if all_valid { 0 } else { 1 })
+}
@@ -1,22 +1,319 @@
-use std::sync::atomic::{AtomicU64, Ordering::SeqCst};
+use std::{
+    env,
+    ops::Range,
+    sync::atomic::{AtomicU64, Ordering::SeqCst},
+};
 
-use gossip_glomers::Node;
-use serde_json::json;
+use gossip_glomers::{
+    ids::{self, Clock, Snowflake, Ulid},
+    Err, Node, KV,
+};
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+
+/// Milliseconds reserved on disk at a time by the time-based formats, a
+/// restart resuming past them whatever its clock says
+const LEASE_MS: u64 = 1000;
+
+/// Most ids a `generate_batch` takes, bounding the memory of a reply and how
+/// long a batch holds up the requests behind it
+const MAX_BATCH: u64 = 10_000;
+
+/// Shape of the ids, named in lowercase by `$UNIQUE_ID_FORMAT`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// `<node>-<epoch>-<counter>` strings
+    Counter,
+    /// 64-bit Snowflake ids, sorting by creation time
+    Snowflake,
+    /// 128-bit ULIDs, sorting by creation time
+    Ulid,
+    /// Integers unique cluster-wide, leased in blocks from lin-kv
+    Blocks,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Config {
+    format: Format,
+    /// Ids sent as JSON numbers rather than strings, `$UNIQUE_ID_JSON` being
+    /// `number` or `string` and numbers the default where they fit
+    numbers: bool,
+    epoch: Epoch,
+    /// Ids leased at a time by the blocks format
+    block: u64,
+}
+
+impl Config {
+    /// From `$UNIQUE_ID_FORMAT`, `$UNIQUE_ID_JSON`, `$UNIQUE_ID_EPOCH` and `$UNIQUE_ID_BLOCK`
+    fn from_env(node: &Node) -> Result<Self, String> {
+        let format = match env::var("UNIQUE_ID_FORMAT").as_deref() {
+            Ok("counter") | Err(_) => Format::Counter,
+            Ok("snowflake") => Format::Snowflake,
+            Ok("ulid") => Format::Ulid,
+            Ok("blocks") => Format::Blocks,
+            Ok(format) => return Err(format!("unknown UNIQUE_ID_FORMAT {format:?}")),
+        };
+        let fits = matches!(format, Format::Snowflake | Format::Blocks);
+        let numbers = match env::var("UNIQUE_ID_JSON").as_deref() {
+            Ok("number") if fits => true,
+            Ok("number") => return Err(format!("{format:?} ids do not fit JSON numbers")),
+            Ok("string") => false,
+            Ok(json) => return Err(format!("unknown UNIQUE_ID_JSON {json:?}")),
+            Err(_) => fits,
+        };
+        let block = match env::var("UNIQUE_ID_BLOCK") {
+            Ok(block) => match block.parse() {
+                Ok(block) if block > 0 => block,
+                _ => return Err(format!("invalid UNIQUE_ID_BLOCK {block:?}")),
+            },
+            Err(_) => 1000,
+        };
+        Ok(Self {
+            format,
+            numbers,
+            epoch: Epoch::from_env(node)?,
+            block,
+        })
+    }
+}
+
+/// Where the epoch numbering each boot of a node comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Epoch {
+    /// The restarts counted in the node state directory
+    Storage,
+    /// A counter per node in lin-kv, bumped with a cas on the first request
+    LinKv,
+}
+
+impl Epoch {
+    /// From `$UNIQUE_ID_EPOCH` (`storage` or `lin-kv`), by default storage if
+    /// durable and lin-kv otherwise
+    fn from_env(node: &Node) -> Result<Self, String> {
+        match env::var("UNIQUE_ID_EPOCH").as_deref() {
+            Ok("storage") if node.storage.is_durable() => Ok(Epoch::Storage),
+            Ok("storage") => Err("UNIQUE_ID_EPOCH storage needs a state directory".to_owned()),
+            Ok("lin-kv") => Ok(Epoch::LinKv),
+            Ok(epoch) => Err(format!("unknown UNIQUE_ID_EPOCH {epoch:?}")),
+            Err(_) if node.storage.is_durable() => Ok(Epoch::Storage),
+            Err(_) => Ok(Epoch::LinKv),
+        }
+    }
+}
+
+/// Take the next `by` values of a lin-kv counter starting at 0, a timed out
+/// cas at worst skipping some, and aborting once they run out
+fn bump(node: &Node, key: &str, by: u64) -> Result<Range<u64>, Err> {
+    loop {
+        let prev = match node.read::<u64>(KV::Lin, key) {
+            Ok(prev) => Some(prev),
+            Err(Err::KeyDoesNotExist) => None,
+            Err(e) => return Err(e),
+        };
+        let from = prev.unwrap_or(0);
+        let to = from.checked_add(by).ok_or(Err::Abort)?;
+        match node.cap(KV::Lin, key, prev, to, prev.is_none()) {
+            Ok(()) => return Ok(from..to),
+            Err(Err::PreconditionFailed | Err::KeyAlreadyExists | Err::Timeout) => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Counter ids of the current boot, once its epoch is known
+struct Counter<'a> {
+    node: &'a Node,
+    epoch: Mutex<Option<u64>>,
+    counter: AtomicU64,
+}
+
+impl Counter<'_> {
+    fn take(&self, n: u64) -> Result<Vec<Value>, Err> {
+        // No id is handed out before the epoch of this boot is known, which
+        // makes a restart start over from a counter of 0 safely
+        let curr = {
+            let mut epoch = self.epoch.lock();
+            match *epoch {
+                Some(curr) => curr,
+                None => {
+                    let key = format!("epoch-{}", self.node.id);
+                    *epoch.insert(bump(self.node, &key, 1)?.start)
+                }
+            }
+        };
+        // Never wrapping around to counters already handed out
+        let from = self
+            .counter
+            .fetch_update(SeqCst, SeqCst, |from| from.checked_add(n))
+            .map_err(|_| Err::Abort)?;
+        let ids = (from..from + n).map(|i| json!(format_id(&self.node.id, curr, i)));
+        Ok(ids.collect())
+    }
+}
+
+/// Integer ids from blocks leased off a cluster-wide lin-kv counter, the
+/// rest of a block being lost on a crash
+struct Blocks<'a> {
+    node: &'a Node,
+    size: u64,
+    numbers: bool,
+    curr: Mutex<Range<u64>>,
+}
+
+impl Blocks<'_> {
+    fn take(&self, n: u64) -> Result<Vec<Value>, Err> {
+        let mut curr = self.curr.lock();
+        let mut ids = Vec::with_capacity(n as usize);
+        while (ids.len() as u64) < n {
+            match curr.next() {
+                Some(id) if self.numbers => ids.push(json!(id)),
+                Some(id) => ids.push(json!(id.to_string())),
+                // A single lease covers a batch larger than a block
+                None => *curr = bump(self.node, "ids", self.size.max(n - ids.len() as u64))?,
+            }
+        }
+        Ok(ids)
+    }
+}
+
+/// Time-based ids, their clock resuming after the lease saved before a
+/// restart, if the storage is durable
+struct Timed<'a> {
+    node: &'a Node,
+    numbers: bool,
+    gen: Mutex<(Generator, u64)>,
+}
+
+enum Generator {
+    Snowflake(Snowflake),
+    Ulid(Ulid),
+}
+
+impl Generator {
+    fn clock(&mut self) -> &mut Clock {
+        match self {
+            Generator::Snowflake(gen) => &mut gen.clock,
+            Generator::Ulid(gen) => &mut gen.clock,
+        }
+    }
+}
+
+impl<'a> Timed<'a> {
+    fn new(node: &'a Node, numbers: bool, mut gen: Generator) -> Self {
+        let lease = node.storage.load("lease").unwrap().unwrap_or(0);
+        gen.clock().resume(lease);
+        Self {
+            node,
+            numbers,
+            gen: Mutex::new((gen, lease)),
+        }
+    }
+
+    fn take(&self, n: u64) -> Vec<Value> {
+        let mut guard = self.gen.lock();
+        let (gen, lease) = &mut *guard;
+        let now = ids::now_ms();
+        let ids = (0..n)
+            .map(|_| match gen {
+                Generator::Snowflake(gen) if self.numbers => json!(gen.next(now)),
+                Generator::Snowflake(gen) => json!(gen.next(now).to_string()),
+                Generator::Ulid(gen) => json!(Ulid::encode(gen.next(now))),
+            })
+            .collect();
+        // Persist the lease before handing out any id past it
+        let last = gen.clock().last();
+        if last >= *lease {
+            *lease = last + LEASE_MS;
+            self.node.storage.save("lease", lease).unwrap();
+        }
+        ids
+    }
+}
+
+enum Ids<'a> {
+    Counter(Counter<'a>),
+    Blocks(Blocks<'a>),
+    Timed(Timed<'a>),
+}
+
+impl<'a> Ids<'a> {
+    fn new(node: &'a Node, config: Config) -> Result<Self, String> {
+        let index = node.node_ids.iter().position(|id| *id == node.id).unwrap();
+        let numbers = config.numbers;
+        Ok(match config.format {
+            Format::Counter => Ids::Counter(Counter {
+                node,
+                epoch: Mutex::new(match config.epoch {
+                    Epoch::Storage => Some(node.boot),
+                    Epoch::LinKv => None,
+                }),
+                counter: AtomicU64::new(0),
+            }),
+            Format::Blocks => Ids::Blocks(Blocks {
+                node,
+                size: config.block,
+                numbers,
+                curr: Mutex::new(0..0),
+            }),
+            Format::Snowflake => Ids::Timed(Timed::new(
+                node,
+                numbers,
+                Generator::Snowflake(Snowflake::new(index)?),
+            )),
+            Format::Ulid => Ids::Timed(Timed::new(
+                node,
+                numbers,
+                Generator::Ulid(Ulid::new(index)?),
+            )),
+        })
+    }
+
+    fn take(&self, n: u64) -> Result<Vec<Value>, Err> {
+        match self {
+            Ids::Counter(ids) => ids.take(n),
+            Ids::Blocks(ids) => ids.take(n),
+            Ids::Timed(ids) => Ok(ids.take(n)),
+        }
+    }
+}
+
+/// Id `<node>-<epoch>-<counter>`, unambiguous as the last two fields are
+/// digits only, whatever the node id
+fn format_id(node: &str, epoch: u64, counter: u64) -> String {
+    format!("{node}-{epoch}-{counter}")
+}
 
 fn main() {
-    let counter = AtomicU64::new(0);
     let node = &Node::new();
+    let ids = Config::from_env(node)
+        .and_then(|config| Ids::new(node, config))
+        .unwrap();
     node.run(|msg| match msg.body["type"].as_str().unwrap() {
-        "generate" => {
-            let curr = counter.fetch_add(1, SeqCst);
-            let id = format!("{}{}", node.id, curr);
-            node.reply(
+        "generate" => match ids.take(1) {
+            Ok(mut taken) => node.reply(
                 &msg,
                 json!({
                     "type": "generate_ok",
-                    "id": id
+                    "id": taken.pop()
                 }),
-            )
+            ),
+            Err(e) => node.reply(&msg, e.msg()),
+        },
+        // Extension handing out `count` ids at once, up to `MAX_BATCH`
+        "generate_batch" => {
+            let Some(count) = msg.body["count"].as_u64().filter(|n| *n <= MAX_BATCH) else {
+                return node.reply(&msg, Err::MalformedRequest.msg());
+            };
+            match ids.take(count) {
+                Ok(taken) => node.reply(
+                    &msg,
+                    json!({
+                        "type": "generate_batch_ok",
+                        "ids": taken
+                    }),
+                ),
+                Err(e) => node.reply(&msg, e.msg()),
+            }
         }
         ty => unreachable!("msg type {ty}"),
     });
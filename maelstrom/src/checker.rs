@@ -0,0 +1,6 @@
+pub mod broadcast;
+pub mod elle;
+pub mod kafka;
+pub mod linearizable;
+pub mod pn_counter;
+pub mod unique_ids;
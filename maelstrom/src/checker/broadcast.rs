@@ -0,0 +1,133 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::history::{History, Type};
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Report {
+    pub valid: bool,
+    pub attempted: usize,
+    pub acknowledged: usize,
+    pub final_reads: usize,
+    /// Acknowledged messages missing from some final read, in the order
+    /// they were broadcast
+    pub lost: Vec<Value>,
+    /// Read messages that were never broadcast
+    pub unexpected: Vec<Value>,
+    /// Messages from indeterminate broadcasts that were read anyway
+    pub recovered: usize,
+    /// Quantiles 0, 0.5, 0.95, 0.99 and 1 of the delay in milliseconds
+    /// between a broadcast and the point every later read contains it
+    pub stable_latencies: BTreeMap<String, f64>,
+}
+
+/// Check that every acknowledged message is in the final reads, and measure
+/// how long messages take to become stable
+///
+/// Final reads are the last read of each process in the final phase, or
+/// among those invoked after every broadcast completed if unmarked.
+/// Messages are any JSON values, compared by their serialization. A message
+/// broadcast several times counts as acknowledged if any attempt was.
+pub fn check(history: &History) -> Report {
+    let pairs = history.pairs();
+    let mut sent: BTreeMap<String, (u64, Type, &Value)> = BTreeMap::new();
+    let mut reads: Vec<(u64, u64, bool, BTreeSet<String>)> = Vec::new();
+    // Time of the last broadcast event
+    let mut quiet = 0;
+    for pair in &pairs {
+        if pair.invoke.f == "broadcast" {
+            quiet = quiet.max(pair.complete.unwrap_or(pair.invoke).time);
+        }
+        match (pair.invoke.f.as_str(), pair.ty()) {
+            ("broadcast", Type::Fail) => {}
+            ("broadcast", ty) => {
+                let msg = &pair.invoke.value;
+                let key = msg.to_string();
+                // An indeterminate retry does not undo an acknowledgement
+                let stronger = sent
+                    .get(&key)
+                    .is_none_or(|(_, prev, _)| *prev == Type::Info && ty == Type::Ok);
+                if stronger {
+                    sent.insert(key, (pair.invoke.time, ty, msg));
+                }
+            }
+            ("read", Type::Ok) => {
+                let msgs = pair.value().as_array().unwrap();
+                let msgs = msgs.iter().map(|m| m.to_string()).collect();
+                reads.push((
+                    pair.invoke.time,
+                    pair.invoke.process,
+                    pair.invoke.r#final,
+                    msgs,
+                ));
+            }
+            _ => {}
+        }
+    }
+    reads.sort_by_key(|(time, ..)| *time);
+
+    let mut final_reads: BTreeMap<u64, &BTreeSet<String>> = BTreeMap::new();
+    let mut read_msgs: BTreeSet<&String> = BTreeSet::new();
+    let marked = history.has_final();
+    for (time, process, phase, msgs) in &reads {
+        let last_phase = if marked { *phase } else { *time > quiet };
+        if last_phase {
+            final_reads.insert(*process, msgs);
+        }
+        read_msgs.extend(msgs);
+    }
+
+    let mut lost = Vec::new();
+    let mut latencies = Vec::new();
+    for (msg, (time, ty, value)) in &sent {
+        if *ty != Type::Ok {
+            continue;
+        }
+        if final_reads.values().any(|r| !r.contains(msg)) {
+            lost.push((*time, (*value).clone()));
+            continue;
+        }
+        // Stable from the first read following the last one missing it
+        let later = &reads[reads.partition_point(|(t, ..)| t < time)..];
+        let stable = match later.iter().rposition(|(.., r)| !r.contains(msg)) {
+            Some(i) => later.get(i + 1).map(|(t, ..)| *t),
+            None => Some(*time),
+        };
+        if let Some(stable) = stable {
+            latencies.push((stable - time) as f64 / 1e6);
+        }
+    }
+    latencies.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let quantile = |q: f64| {
+        let i = ((latencies.len() as f64 - 1.) * q).round() as usize;
+        latencies.get(i).copied().unwrap_or(0.)
+    };
+    let stable_latencies = [0., 0.5, 0.95, 0.99, 1.]
+        .into_iter()
+        .map(|q| (q.to_string(), quantile(q)))
+        .collect();
+
+    lost.sort_by_key(|(time, _)| *time);
+    let lost: Vec<Value> = lost.into_iter().map(|(_, msg)| msg).collect();
+    let unexpected: Vec<Value> = read_msgs
+        .iter()
+        .filter(|m| !sent.contains_key(**m))
+        .map(|m| serde_json::from_str(m).unwrap())
+        .collect();
+    let recovered = sent
+        .iter()
+        .filter(|(m, (_, ty, _))| *ty == Type::Info && read_msgs.contains(m))
+        .count();
+    Report {
+        valid: lost.is_empty() && unexpected.is_empty() && !final_reads.is_empty(),
+        final_reads: final_reads.len(),
+        attempted: pairs.iter().filter(|p| p.invoke.f == "broadcast").count(),
+        acknowledged: sent.values().filter(|(_, ty, _)| *ty == Type::Ok).count(),
+        lost,
+        unexpected,
+        recovered,
+        stable_latencies,
+    }
+}
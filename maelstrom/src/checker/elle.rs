@@ -0,0 +1,525 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fmt,
+    str::FromStr,
+};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::history::{History, Op, Pair, Type};
+
+/// Transactional workload producing the history
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Workload {
+    /// `["append", k, v]` and `["r", k, [v...]]` micro-operations
+    ListAppend,
+    /// `["w", k, v]` and `["r", k, v]` micro-operations
+    RwRegister,
+}
+
+/// Dependency between two transactions
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Rel {
+    /// Write-write: the second overwrites or appends after the first
+    Ww,
+    /// Write-read: the second observes the first
+    Wr,
+    /// Read-write: the second overwrites what the first observed
+    Rw,
+}
+
+impl Rel {
+    fn bit(self) -> u8 {
+        match self {
+            Rel::Ww => 1,
+            Rel::Wr => 2,
+            Rel::Rw => 4,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Kind {
+    /// Cycle of write-write dependencies
+    G0,
+    /// Read of a value written by an aborted transaction
+    G1a,
+    /// Read of a value later overwritten by the same transaction
+    G1b,
+    /// Cycle of write-write and write-read dependencies
+    G1c,
+    /// Cycle with exactly one anti-dependency
+    #[serde(rename = "G-single")]
+    GSingle,
+    /// Cycle with several anti-dependencies
+    G2,
+    /// Reads of a list that are not prefixes of each other
+    #[serde(rename = "incompatible-order")]
+    IncompatibleOrder,
+}
+
+/// Isolation level the history is checked against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Model {
+    ReadUncommitted,
+    ReadCommitted,
+    SnapshotIsolation,
+    RepeatableRead,
+    Serializable,
+}
+
+impl Model {
+    pub fn prohibits(self, kind: Kind) -> bool {
+        use Kind::*;
+        match self {
+            Model::ReadUncommitted => matches!(kind, G0 | IncompatibleOrder),
+            Model::ReadCommitted => matches!(kind, G0 | G1a | G1b | G1c | IncompatibleOrder),
+            Model::SnapshotIsolation => kind != G2,
+            Model::RepeatableRead | Model::Serializable => true,
+        }
+    }
+}
+
+impl FromStr for Model {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "read-uncommitted" => Model::ReadUncommitted,
+            "read-committed" => Model::ReadCommitted,
+            "snapshot-isolation" => Model::SnapshotIsolation,
+            "repeatable-read" => Model::RepeatableRead,
+            "serializable" => Model::Serializable,
+            unknown => return Err(format!("unknown consistency model {unknown}")),
+        })
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let json = serde_json::to_value(self).unwrap();
+        f.write_str(json.as_str().unwrap())
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Anomaly {
+    pub kind: Kind,
+    /// Completions of the involved transactions, in cycle order for cycles
+    pub txns: Vec<Op>,
+    /// Dependency from each transaction to the next one of the cycle
+    pub rels: Vec<Rel>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Report {
+    pub valid: bool,
+    pub anomaly_types: BTreeSet<Kind>,
+    pub anomalies: Vec<Anomaly>,
+}
+
+struct Txn<'a> {
+    pair: Pair<'a>,
+    ty: Type,
+    mops: Vec<(&'a str, String, &'a Value)>,
+}
+
+impl Txn<'_> {
+    fn op(&self) -> Op {
+        self.pair.complete.unwrap_or(self.pair.invoke).clone()
+    }
+}
+
+/// Dependency graph between transactions, edges labelled with a set of `Rel`
+#[derive(Default)]
+struct Graph {
+    edges: BTreeMap<usize, BTreeMap<usize, u8>>,
+}
+
+impl Graph {
+    fn link(&mut self, from: usize, to: usize, rel: Rel) {
+        if from != to {
+            *self.edges.entry(from).or_default().entry(to).or_default() |= rel.bit();
+        }
+    }
+
+    fn next(&self, from: usize, mask: u8) -> impl Iterator<Item = usize> + '_ {
+        self.edges
+            .get(&from)
+            .into_iter()
+            .flatten()
+            .filter(move |(_, rels)| *rels & mask != 0)
+            .map(|(to, _)| *to)
+    }
+
+    /// Strongest relation of an edge among the allowed ones
+    fn rel(&self, from: usize, to: usize, mask: u8) -> Rel {
+        let rels = self.edges[&from][&to] & mask;
+        [Rel::Ww, Rel::Wr, Rel::Rw]
+            .into_iter()
+            .find(|r| rels & r.bit() != 0)
+            .unwrap()
+    }
+
+    /// Shortest path using edges in `mask`, both ends included
+    fn path(&self, from: usize, to: usize, mask: u8) -> Option<Vec<usize>> {
+        let mut prev = BTreeMap::from([(from, from)]);
+        let mut queue = VecDeque::from([from]);
+        while let Some(curr) = queue.pop_front() {
+            if curr == to {
+                let mut path = vec![to];
+                let mut at = to;
+                while at != from {
+                    at = prev[&at];
+                    path.push(at);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for next in self.next(curr, mask) {
+                prev.entry(next).or_insert_with(|| {
+                    queue.push_back(next);
+                    curr
+                });
+            }
+        }
+        None
+    }
+
+    /// Cycle starting with the edge `from -> to`, closed by a path restricted
+    /// to `mask`
+    fn cycle_through(&self, from: usize, to: usize, mask: u8) -> Option<Vec<usize>> {
+        let mut path = self.path(to, from, mask)?;
+        path.pop();
+        let mut cycle = vec![from];
+        cycle.extend(path);
+        Some(cycle)
+    }
+}
+
+/// Check a transactional history for Adya anomalies
+///
+/// Dependencies are inferred as in Elle: list-append reads reveal the whole
+/// version order of a key, while rw-register reads only reveal the order of
+/// the initial nil version and of writes following a read in the same
+/// transaction. Failed transactions are only used to detect aborted reads
+/// and indeterminate ones only contribute their writes.
+pub fn check(history: &History, workload: Workload, model: Model) -> Report {
+    let pairs = history.pairs();
+    let txns: Vec<Txn> = pairs
+        .iter()
+        .filter(|p| p.invoke.f == "txn")
+        .map(|pair| Txn {
+            pair: *pair,
+            ty: pair.ty(),
+            mops: pair
+                .value()
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|m| (m[0].as_str().unwrap(), m[1].to_string(), &m[2]))
+                .collect(),
+        })
+        .collect();
+
+    let mut anomalies = Vec::new();
+    let mut graph = Graph::default();
+    match workload {
+        Workload::ListAppend => list_append(&txns, &mut graph, &mut anomalies),
+        Workload::RwRegister => rw_register(&txns, &mut graph, &mut anomalies),
+    }
+    cycles(&txns, &graph, &mut anomalies);
+
+    let anomaly_types: BTreeSet<Kind> = anomalies.iter().map(|a| a.kind).collect();
+    Report {
+        valid: !anomaly_types.iter().any(|k| model.prohibits(*k)),
+        anomaly_types,
+        anomalies,
+    }
+}
+
+fn anomaly(kind: Kind, txns: &[Txn], ids: &[usize], rels: Vec<Rel>) -> Anomaly {
+    Anomaly {
+        kind,
+        txns: ids.iter().map(|i| txns[*i].op()).collect(),
+        rels,
+    }
+}
+
+/// Writer of each (key, value) and whether it was its last write of the key
+type Writers = BTreeMap<(String, String), (usize, bool)>;
+
+fn writers(txns: &[Txn], write: &str) -> Writers {
+    let mut writers = BTreeMap::new();
+    for (i, txn) in txns.iter().enumerate() {
+        let mut last = BTreeMap::new();
+        for (f, k, v) in &txn.mops {
+            if *f == write {
+                last.insert(k.clone(), v.to_string());
+                writers.insert((k.clone(), v.to_string()), (i, false));
+            }
+        }
+        for (k, v) in last {
+            writers.get_mut(&(k, v)).unwrap().1 = true;
+        }
+    }
+    writers
+}
+
+/// Check an observed value against its writer for aborted reads, and for
+/// intermediate reads if it is the latest version observed
+fn check_read(
+    txns: &[Txn],
+    writers: &Writers,
+    reader: usize,
+    (key, value): (&str, String),
+    latest: bool,
+    anomalies: &mut Vec<Anomaly>,
+) -> Option<usize> {
+    let (writer, last) = *writers.get(&(key.to_owned(), value))?;
+    if writer != reader {
+        if txns[writer].ty == Type::Fail {
+            anomalies.push(anomaly(Kind::G1a, txns, &[reader, writer], vec![]));
+        } else if latest && !last {
+            anomalies.push(anomaly(Kind::G1b, txns, &[reader, writer], vec![]));
+        }
+    }
+    (txns[writer].ty != Type::Fail).then_some(writer)
+}
+
+fn list_append(txns: &[Txn], graph: &mut Graph, anomalies: &mut Vec<Anomaly>) {
+    let writers = writers(txns, "append");
+
+    // External reads of committed transactions, before any own append to the key
+    let mut reads: BTreeMap<String, Vec<(usize, Vec<String>)>> = BTreeMap::new();
+    for (i, txn) in txns.iter().enumerate().filter(|(_, t)| t.ty == Type::Ok) {
+        let mut written = BTreeSet::new();
+        for (f, k, v) in &txn.mops {
+            match *f {
+                "append" => {
+                    written.insert(k);
+                }
+                "r" if !written.contains(k) => {
+                    let list = v.as_array().into_iter().flatten();
+                    reads
+                        .entry(k.clone())
+                        .or_default()
+                        .push((i, list.map(|v| v.to_string()).collect()));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    for (key, reads) in reads {
+        // Longest read gives the version order, every other read must be a prefix
+        let (longest_reader, order) = reads.iter().max_by_key(|(_, l)| l.len()).unwrap();
+        for (reader, list) in &reads {
+            if !order.starts_with(list) {
+                let ids = [*reader, *longest_reader];
+                anomalies.push(anomaly(Kind::IncompatibleOrder, txns, &ids, vec![]));
+            }
+        }
+        let writer_of = |v: &String| {
+            writers
+                .get(&(key.clone(), v.clone()))
+                .map(|w| w.0)
+                .filter(|w| txns[*w].ty != Type::Fail)
+        };
+        for pair in order.windows(2) {
+            if let (Some(a), Some(b)) = (writer_of(&pair[0]), writer_of(&pair[1])) {
+                graph.link(a, b, Rel::Ww);
+            }
+        }
+        for (reader, list) in &reads {
+            if !order.starts_with(list) {
+                continue;
+            }
+            for (i, v) in list.iter().enumerate() {
+                let latest = i + 1 == list.len();
+                let writer = check_read(
+                    txns,
+                    &writers,
+                    *reader,
+                    (&key, v.clone()),
+                    latest,
+                    anomalies,
+                );
+                if let Some(writer) = writer.filter(|_| latest) {
+                    graph.link(writer, *reader, Rel::Wr);
+                }
+            }
+            if let Some(writer) = order.get(list.len()).and_then(writer_of) {
+                graph.link(*reader, writer, Rel::Rw);
+            }
+        }
+    }
+}
+
+fn rw_register(txns: &[Txn], graph: &mut Graph, anomalies: &mut Vec<Anomaly>) {
+    let writers = writers(txns, "w");
+    let mut writers_of_key: BTreeMap<&str, BTreeSet<usize>> = BTreeMap::new();
+    for ((k, _), (w, _)) in &writers {
+        if txns[*w].ty != Type::Fail {
+            writers_of_key.entry(k).or_default().insert(*w);
+        }
+    }
+    // Readers of each (key, value), to derive anti-dependencies
+    let mut readers: BTreeMap<(String, String), BTreeSet<usize>> = BTreeMap::new();
+    // Versions known to directly precede a write: (key, read value, writer)
+    let mut successors = Vec::new();
+
+    for (i, txn) in txns.iter().enumerate().filter(|(_, t)| t.ty == Type::Ok) {
+        let mut observed: BTreeMap<&String, String> = BTreeMap::new();
+        let mut written = BTreeSet::new();
+        for (f, k, v) in &txn.mops {
+            match *f {
+                "r" if !written.contains(k) && !observed.contains_key(k) => {
+                    observed.insert(k, v.to_string());
+                    readers
+                        .entry((k.clone(), v.to_string()))
+                        .or_default()
+                        .insert(i);
+                    if !v.is_null() {
+                        if let Some(w) =
+                            check_read(txns, &writers, i, (k, v.to_string()), true, anomalies)
+                        {
+                            graph.link(w, i, Rel::Wr);
+                        }
+                    }
+                }
+                "w" if written.insert(k) => {
+                    if let Some(prev) = observed.get(k) {
+                        successors.push((k.clone(), prev.clone(), i));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // The initial nil version precedes every write
+    for ((k, v), rs) in &readers {
+        if v == "null" {
+            for r in rs {
+                for w in writers_of_key.get(k.as_str()).into_iter().flatten() {
+                    graph.link(*r, *w, Rel::Rw);
+                }
+            }
+        }
+    }
+    // A write following a read in the same transaction overwrites that version
+    for (k, prev, w) in successors {
+        if let Some((prev_writer, _)) = writers.get(&(k.clone(), prev.clone())) {
+            graph.link(*prev_writer, w, Rel::Ww);
+        }
+        for r in readers.get(&(k, prev)).into_iter().flatten() {
+            graph.link(*r, w, Rel::Rw);
+        }
+    }
+}
+
+/// Strongly connected components with more than one transaction
+fn components(graph: &Graph, n: usize) -> Vec<BTreeSet<usize>> {
+    // Kosaraju: order by finish time, then explore the transposed graph
+    let all = Rel::Ww.bit() | Rel::Wr.bit() | Rel::Rw.bit();
+    let mut seen = vec![false; n];
+    let mut order = Vec::with_capacity(n);
+    for root in 0..n {
+        if seen[root] {
+            continue;
+        }
+        seen[root] = true;
+        let mut stack = vec![(root, graph.next(root, all).collect::<Vec<_>>())];
+        while let Some((node, children)) = stack.last_mut() {
+            match children.pop() {
+                Some(child) if !seen[child] => {
+                    seen[child] = true;
+                    let next = graph.next(child, all).collect();
+                    stack.push((child, next));
+                }
+                Some(_) => {}
+                None => {
+                    order.push(*node);
+                    stack.pop();
+                }
+            }
+        }
+    }
+    let mut transposed: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for (from, tos) in &graph.edges {
+        for to in tos.keys() {
+            transposed.entry(*to).or_default().push(*from);
+        }
+    }
+    let mut assigned = vec![false; n];
+    let mut components = Vec::new();
+    for root in order.into_iter().rev() {
+        if assigned[root] {
+            continue;
+        }
+        assigned[root] = true;
+        let mut component = BTreeSet::from([root]);
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            for prev in transposed.get(&node).into_iter().flatten() {
+                if !assigned[*prev] {
+                    assigned[*prev] = true;
+                    component.insert(*prev);
+                    stack.push(*prev);
+                }
+            }
+        }
+        if component.len() > 1 {
+            components.push(component);
+        }
+    }
+    components
+}
+
+/// Report the strongest cycle anomaly of each strongly connected component
+fn cycles(txns: &[Txn], graph: &Graph, anomalies: &mut Vec<Anomaly>) {
+    let (ww, wr, rw) = (Rel::Ww.bit(), Rel::Wr.bit(), Rel::Rw.bit());
+    for component in components(graph, txns.len()) {
+        // Edges inside the component, labelled
+        let edges: Vec<(usize, usize, u8)> = component
+            .iter()
+            .flat_map(|from| {
+                graph.edges[from]
+                    .iter()
+                    .filter(|(to, _)| component.contains(to))
+                    .map(|(to, rels)| (*from, *to, *rels))
+            })
+            .collect();
+        // Try the classes from the most to the least severe: the first edge
+        // must carry the class relation, the closing path the allowed ones
+        let classes = [
+            (Kind::G0, ww, ww),
+            (Kind::G1c, wr, ww | wr),
+            (Kind::GSingle, rw, ww | wr),
+            (Kind::G2, rw, ww | wr | rw),
+        ];
+        for (kind, first, mask) in classes {
+            let found = edges
+                .iter()
+                .filter(|(_, _, rels)| rels & first != 0)
+                .find_map(|(from, to, _)| graph.cycle_through(*from, *to, mask));
+            if let Some(cycle) = found {
+                let rels = (0..cycle.len())
+                    .map(|i| {
+                        let (a, b) = (cycle[i], cycle[(i + 1) % cycle.len()]);
+                        if i == 0 {
+                            graph.rel(a, b, first)
+                        } else {
+                            graph.rel(a, b, mask)
+                        }
+                    })
+                    .collect();
+                anomalies.push(anomaly(kind, txns, &cycle, rels));
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+
+use crate::history::{History, Op, Type};
+
+/// Known offsets skipped by a poll of a key
+#[derive(Serialize, Debug, Clone)]
+pub struct Gap {
+    pub key: String,
+    pub process: u64,
+    /// Last offset returned before the gap, None if the gap is right after
+    /// the requested offset
+    pub after: Option<u64>,
+    pub before: u64,
+    pub skipped: Vec<u64>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Report {
+    pub valid: bool,
+    pub sends: usize,
+    pub polls: usize,
+    /// Acknowledged `(key, offset, msg)` never polled although later offsets were
+    pub lost: Vec<(String, u64, u64)>,
+    /// Offsets of a key bound to several messages
+    pub inconsistent: BTreeMap<String, BTreeMap<u64, BTreeSet<u64>>>,
+    /// Polls returning the offsets of a key out of order
+    pub nonmonotonic: Vec<Op>,
+    pub gaps: Vec<Gap>,
+}
+
+/// Check a kafka history
+///
+/// A send is invoked with `[key, msg]` and completes with `[key, msg, offset]`,
+/// a poll is invoked with the requested offsets and completes with the
+/// `msgs` of `poll_ok`, a map from keys to `[offset, msg]` pairs.
+pub fn check(history: &History) -> Report {
+    let mut sends = Vec::new();
+    let mut polls = Vec::new();
+    // Every message seen at each offset of each key
+    let mut offsets: BTreeMap<String, BTreeMap<u64, BTreeSet<u64>>> = BTreeMap::new();
+    for pair in history.pairs() {
+        let value = pair.value();
+        match (pair.invoke.f.as_str(), pair.ty()) {
+            ("send", Type::Ok) => {
+                let key = value[0].as_str().unwrap().to_owned();
+                let (msg, offset) = (value[1].as_u64().unwrap(), value[2].as_u64().unwrap());
+                offsets
+                    .entry(key.clone())
+                    .or_default()
+                    .entry(offset)
+                    .or_default()
+                    .insert(msg);
+                sends.push((key, offset, msg));
+            }
+            ("poll", Type::Ok) => {
+                let msgs: BTreeMap<String, Vec<(u64, u64)>> =
+                    serde_json::from_value(value.clone()).unwrap();
+                for (key, msgs) in &msgs {
+                    for (offset, msg) in msgs {
+                        offsets
+                            .entry(key.clone())
+                            .or_default()
+                            .entry(*offset)
+                            .or_default()
+                            .insert(*msg);
+                    }
+                }
+                polls.push((pair.invoke, msgs));
+            }
+            _ => {}
+        }
+    }
+
+    let mut polled: BTreeSet<(&str, u64)> = BTreeSet::new();
+    let mut highest: BTreeMap<&str, u64> = BTreeMap::new();
+    let mut nonmonotonic = Vec::new();
+    let mut gaps = Vec::new();
+    for (invoke, msgs) in &polls {
+        for (key, msgs) in msgs {
+            let known = &offsets[key];
+            let requested = invoke.value[key].as_u64();
+            if msgs.windows(2).any(|w| w[0].0 >= w[1].0) {
+                nonmonotonic.push((*invoke).clone());
+                continue;
+            }
+            let mut prev: Option<u64> = None;
+            for (offset, _) in msgs {
+                polled.insert((key, *offset));
+                let max = highest.entry(key).or_default();
+                *max = (*max).max(*offset);
+                // Known offsets between the previous one returned and this one
+                let skipped: Vec<u64> = match (prev, requested) {
+                    (Some(p), _) => known.range(p + 1..*offset).map(|(o, _)| *o).collect(),
+                    (None, Some(r)) if r < *offset => {
+                        known.range(r..*offset).map(|(o, _)| *o).collect()
+                    }
+                    (None, _) => Vec::new(),
+                };
+                if !skipped.is_empty() {
+                    gaps.push(Gap {
+                        key: key.clone(),
+                        process: invoke.process,
+                        after: prev,
+                        before: *offset,
+                        skipped,
+                    });
+                }
+                prev = Some(*offset);
+            }
+        }
+    }
+
+    let lost = sends
+        .iter()
+        .filter(|(key, offset, _)| {
+            !polled.contains(&(key.as_str(), *offset))
+                && highest.get(key.as_str()).is_some_and(|max| offset < max)
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    for msgs in offsets.values_mut() {
+        msgs.retain(|_, msgs| msgs.len() > 1);
+    }
+    offsets.retain(|_, msgs| !msgs.is_empty());
+    Report {
+        valid: lost.is_empty() && offsets.is_empty() && nonmonotonic.is_empty() && gaps.is_empty(),
+        sends: sends.len(),
+        polls: polls.len(),
+        lost,
+        inconsistent: offsets,
+        nonmonotonic,
+        gaps,
+    }
+}
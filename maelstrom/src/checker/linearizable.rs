@@ -0,0 +1,215 @@
+use std::collections::{BTreeMap, HashSet};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::history::{History, Op, Pair, Type};
+
+/// Register state, `Any` standing for an unknown initial value when
+/// checking a window cut out of the history
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Reg {
+    Any,
+    Missing,
+    Is(String),
+}
+
+#[derive(Debug, Clone)]
+enum Call {
+    /// Observed value, None if the read outcome is unknown
+    Read(Option<String>),
+    Write(String),
+    Cas(String, String),
+}
+
+impl Call {
+    fn step(&self, state: &Reg) -> Option<Reg> {
+        match (self, state) {
+            (Call::Read(None), _) => Some(state.clone()),
+            (Call::Read(Some(v)), Reg::Any) => Some(Reg::Is(v.clone())),
+            (Call::Read(Some(v)), Reg::Is(curr)) => (v == curr).then(|| state.clone()),
+            (Call::Read(Some(v)), Reg::Missing) => (v == "null").then_some(Reg::Missing),
+            (Call::Write(v), _) => Some(Reg::Is(v.clone())),
+            (Call::Cas(_, to), Reg::Any) => Some(Reg::Is(to.clone())),
+            (Call::Cas(from, to), Reg::Is(curr)) => (from == curr).then(|| Reg::Is(to.clone())),
+            (Call::Cas(..), Reg::Missing) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry<'a> {
+    pair: Pair<'a>,
+    call: Call,
+    /// History index of the invocation
+    start: usize,
+    /// History index of the completion, None if the operation is optional
+    end: Option<usize>,
+}
+
+/// Smallest part of a key history admitting no linearization
+#[derive(Serialize, Debug, Clone)]
+pub struct Failure {
+    pub key: Value,
+    /// Events of the window, invocations and completions
+    pub ops: Vec<Op>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Report {
+    pub valid: bool,
+    pub keys: usize,
+    pub failures: Vec<Failure>,
+}
+
+/// Check a lin-kv history of read, write and cas operations, each value being
+/// `[key, value]` or `[key, [from, to]]` for cas
+///
+/// Keys are independent registers and are checked separately with the
+/// Wing-Gong search, memoizing visited (linearized set, state) configurations
+/// as in Lowe's and Porcupine's variants.
+pub fn check(history: &History) -> Report {
+    let mut keys: BTreeMap<String, (Value, Vec<Entry>)> = BTreeMap::new();
+    for pair in history.pairs() {
+        let ty = pair.ty();
+        if ty == Type::Fail {
+            // Failed operations certainly did not happen
+            continue;
+        }
+        let value = pair.value();
+        let arg = |v: &Value| v.to_string();
+        let call = match pair.invoke.f.as_str() {
+            "read" if ty == Type::Ok => Call::Read(Some(arg(&value[1]))),
+            "read" => Call::Read(None),
+            "write" => Call::Write(arg(&value[1])),
+            "cas" => Call::Cas(arg(&value[1][0]), arg(&value[1][1])),
+            _ => continue,
+        };
+        let entry = Entry {
+            pair,
+            call,
+            start: pair.invoke.index,
+            end: pair
+                .complete
+                .filter(|op| op.ty == Type::Ok)
+                .map(|op| op.index),
+        };
+        keys.entry(value[0].to_string())
+            .or_insert_with(|| (value[0].clone(), Vec::new()))
+            .1
+            .push(entry);
+    }
+
+    let mut failures = Vec::new();
+    for (key, mut entries) in keys.values().cloned() {
+        entries.sort_by_key(|e| e.start);
+        if !linearizable(&entries, Reg::Missing) {
+            failures.push(Failure {
+                key,
+                ops: minimal_window(&entries),
+            });
+        }
+    }
+    Report {
+        valid: failures.is_empty(),
+        keys: keys.len(),
+        failures,
+    }
+}
+
+/// Depth-first search of a linearization, entries being sorted by start
+fn linearizable(entries: &[Entry], init: Reg) -> bool {
+    let n = entries.len();
+    let required: Vec<bool> = entries.iter().map(|e| e.end.is_some()).collect();
+    let mut memo: HashSet<(Vec<u64>, Reg)> = HashSet::new();
+    let mut stack = vec![(vec![0u64; n.div_ceil(64)], init, 0usize)];
+
+    let is_set = |lin: &[u64], i: usize| lin[i / 64] & (1 << (i % 64)) != 0;
+    while let Some((lin, state, cursor)) = stack.last_mut() {
+        // Earliest completion among the operations still to linearize
+        let min_end = (0..n)
+            .filter(|i| required[*i] && !is_set(lin, *i))
+            .filter_map(|i| entries[i].end)
+            .min();
+        let Some(min_end) = min_end else {
+            // Every completed operation is linearized, optional ones can come last
+            return true;
+        };
+        // Candidates were invoked before the earliest pending completion
+        let next = (*cursor..n)
+            .take_while(|i| entries[*i].start < min_end)
+            .find(|i| !is_set(lin, *i));
+        match next {
+            Some(i) => {
+                *cursor = i + 1;
+                if let Some(state) = entries[i].call.step(state) {
+                    let mut lin = lin.clone();
+                    lin[i / 64] |= 1 << (i % 64);
+                    if memo.insert((lin.clone(), state.clone())) {
+                        stack.push((lin, state, 0));
+                    }
+                }
+            }
+            None => {
+                stack.pop();
+            }
+        }
+    }
+    false
+}
+
+/// Entries of the history as observed at `end`, later completions being unknown
+fn prefix<'a>(entries: &[Entry<'a>], end: usize) -> Vec<Entry<'a>> {
+    entries
+        .iter()
+        .filter(|e| e.start <= end)
+        .map(|e| Entry {
+            end: e.end.filter(|i| *i <= end),
+            call: match (&e.call, e.end) {
+                (Call::Read(_), Some(i)) if i > end => Call::Read(None),
+                (call, _) => call.clone(),
+            },
+            ..e.clone()
+        })
+        .collect()
+}
+
+/// Shrink a non-linearizable history to the shortest prefix, then to the
+/// latest start from which the prefix stays non-linearizable from any state
+fn minimal_window(entries: &[Entry]) -> Vec<Op> {
+    let mut ends: Vec<usize> = entries.iter().filter_map(|e| e.end).collect();
+    ends.sort_unstable();
+    let bad_prefix = ends.partition_point(|end| linearizable(&prefix(entries, *end), Reg::Missing));
+    let end = ends[bad_prefix.min(ends.len() - 1)];
+    let window = prefix(entries, end);
+
+    let starts: Vec<usize> = window.iter().map(|e| e.start).collect();
+    let from = |start: usize| -> Vec<Entry> {
+        window
+            .iter()
+            .filter(|e| e.start >= start)
+            .cloned()
+            .collect()
+    };
+    let window = if linearizable(&window, Reg::Any) {
+        // The violation depends on the key being initially missing
+        window
+    } else {
+        let last_bad = starts.partition_point(|s| !linearizable(&from(*s), Reg::Any));
+        from(starts[last_bad.saturating_sub(1)])
+    };
+
+    let mut ops: Vec<Op> = window
+        .iter()
+        .flat_map(|e| {
+            [
+                Some(e.pair.invoke),
+                e.pair.complete.filter(|op| op.index <= end),
+            ]
+        })
+        .flatten()
+        .cloned()
+        .collect();
+    ops.sort_by_key(|op| op.index);
+    ops
+}
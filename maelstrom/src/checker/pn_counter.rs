@@ -0,0 +1,70 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::history::{History, Type};
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Report {
+    pub valid: bool,
+    /// Range of values a final read may return
+    pub bounds: (i64, i64),
+    /// Last read of each process in the final phase, or among those invoked
+    /// after every add if unmarked
+    pub final_reads: Vec<i64>,
+    pub errors: Vec<i64>,
+}
+
+/// Check that the final reads lie within the sum of acknowledged adds plus
+/// any subset of the indeterminate ones
+pub fn check(history: &History) -> Report {
+    let (mut lower, mut upper) = (0i64, 0i64);
+    let mut reads = Vec::new();
+    // Time of the last add event
+    let mut quiet = 0;
+    for pair in history.pairs() {
+        if pair.invoke.f == "add" {
+            quiet = quiet.max(pair.complete.unwrap_or(pair.invoke).time);
+        }
+        match (pair.invoke.f.as_str(), pair.ty()) {
+            ("add", Type::Ok) => {
+                let delta = pair.value().as_i64().unwrap();
+                lower += delta;
+                upper += delta;
+            }
+            ("add", Type::Info) => {
+                let delta = pair.value().as_i64().unwrap();
+                if delta < 0 {
+                    lower += delta;
+                } else {
+                    upper += delta;
+                }
+            }
+            ("read", Type::Ok) => {
+                reads.push((pair.invoke, pair.value().as_i64().unwrap()));
+            }
+            _ => {}
+        }
+    }
+    let marked = history.has_final();
+    let last_reads: BTreeMap<u64, i64> = reads
+        .into_iter()
+        .filter(|(invoke, _)| match marked {
+            true => invoke.r#final,
+            false => invoke.time > quiet,
+        })
+        .map(|(invoke, value)| (invoke.process, value))
+        .collect();
+    let final_reads: Vec<i64> = last_reads.into_values().collect();
+    let errors: Vec<i64> = final_reads
+        .iter()
+        .copied()
+        .filter(|v| *v < lower || upper < *v)
+        .collect();
+    Report {
+        valid: errors.is_empty() && !final_reads.is_empty(),
+        bounds: (lower, upper),
+        final_reads,
+        errors,
+    }
+}
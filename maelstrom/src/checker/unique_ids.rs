@@ -0,0 +1,37 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::history::{History, Type};
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Report {
+    pub valid: bool,
+    pub attempted: usize,
+    pub acknowledged: usize,
+    /// Ids returned more than once, with their number of occurrences
+    pub duplicated: BTreeMap<String, usize>,
+}
+
+/// Check that every acknowledged `generate` returned a distinct id
+pub fn check(history: &History) -> Report {
+    let mut ids: BTreeMap<String, usize> = BTreeMap::new();
+    let mut attempted = 0;
+    for pair in history.pairs() {
+        if pair.invoke.f != "generate" {
+            continue;
+        }
+        attempted += 1;
+        if pair.ty() == Type::Ok {
+            *ids.entry(pair.value().to_string()).or_default() += 1;
+        }
+    }
+    let acknowledged = ids.values().sum();
+    ids.retain(|_, count| *count > 1);
+    Report {
+        valid: ids.is_empty(),
+        attempted,
+        acknowledged,
+        duplicated: ids,
+    }
+}
@@ -0,0 +1,195 @@
+use std::{
+    collections::BTreeMap,
+    fs::OpenOptions,
+    io::{self, BufRead, BufReader, Write},
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    thread::spawn,
+    time::Duration,
+};
+
+use parking_lot::Mutex;
+use serde_json::json;
+
+use crate::{
+    generator::{self, Client},
+    nemesis::Lifecycle,
+    net::{self, Net},
+    storage::STATE_DIR,
+    Err, Msg, Node,
+};
+
+/// In-process cluster of nodes named `n0`, `n1`... each running `main` on
+/// its own thread, as a binary would with `Node::new()`
+pub struct Cluster {
+    pub net: Net,
+    pub nodes: Vec<String>,
+    main: fn(Node),
+}
+
+impl Cluster {
+    pub fn new(config: net::Config, n: usize, main: fn(Node)) -> Result<Self, Err> {
+        let tmp = Self {
+            net: Net::new(config),
+            nodes: (0..n).map(|i| format!("n{i}")).collect(),
+            main,
+        };
+        for id in &tmp.nodes {
+            tmp.start(id);
+        }
+        generator::init(&tmp.net, &tmp.nodes)?;
+        Ok(tmp)
+    }
+
+    fn start(&self, id: &str) {
+        let (receiver, sender) = self.net.join(id);
+        let main = self.main;
+        spawn(move || main(Node::from_channels(receiver, sender)));
+    }
+}
+
+impl Lifecycle for Cluster {
+    /// Disconnect the node, whose `run` then returns and drops its state,
+    /// background loops such as gossip and failure detection stopping too
+    fn kill(&self, node: &str) {
+        self.net.leave(node);
+    }
+
+    fn restart(&self, node: &str) {
+        self.start(node);
+        let body = json!({"type": "init", "node_id": node, "node_ids": self.nodes});
+        Client::new(&self.net, "nemesis")
+            .rpc(node, body, Duration::from_secs(10))
+            .ok();
+    }
+}
+
+/// Cluster of node binaries run as child processes, exchanging JSON lines
+/// over their stdin and stdout as with maelstrom
+pub struct Processes {
+    pub net: Net,
+    pub nodes: Vec<String>,
+    bin: PathBuf,
+    /// Directory of the `<node>.log` stderr files, inherited if None
+    logs: Option<PathBuf>,
+    /// Root of the node state directories, left intact across restarts
+    state: Option<PathBuf>,
+    /// Environment variables set for the nodes, e.g. their config
+    env: Vec<(String, String)>,
+    children: Mutex<BTreeMap<String, Child>>,
+}
+
+impl Processes {
+    pub fn new(
+        config: net::Config,
+        n: usize,
+        bin: PathBuf,
+        logs: Option<PathBuf>,
+        state: Option<PathBuf>,
+    ) -> io::Result<Self> {
+        Self::with_env(config, n, bin, logs, state, Vec::new())
+    }
+
+    /// Nodes started with extra environment variables, on top of ours
+    pub fn with_env(
+        config: net::Config,
+        n: usize,
+        bin: PathBuf,
+        logs: Option<PathBuf>,
+        state: Option<PathBuf>,
+        env: Vec<(String, String)>,
+    ) -> io::Result<Self> {
+        let tmp = Self {
+            net: Net::new(config),
+            nodes: (0..n).map(|i| format!("n{i}")).collect(),
+            bin,
+            logs,
+            state,
+            env,
+            children: Mutex::new(BTreeMap::new()),
+        };
+        for id in &tmp.nodes {
+            tmp.start(id)?;
+        }
+        generator::init(&tmp.net, &tmp.nodes)
+            .map_err(|e| io::Error::other(format!("init failed: {e:?}")))?;
+        Ok(tmp)
+    }
+
+    fn start(&self, id: &str) -> io::Result<()> {
+        let stderr = match &self.logs {
+            Some(dir) => {
+                let path = dir.join(format!("{id}.log"));
+                Stdio::from(OpenOptions::new().create(true).append(true).open(path)?)
+            }
+            None => Stdio::inherit(),
+        };
+        let mut cmd = Command::new(&self.bin);
+        cmd.envs(self.env.iter().cloned());
+        if let Some(state) = &self.state {
+            cmd.env(STATE_DIR, state);
+        }
+        let mut child = cmd
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(stderr)
+            .spawn()?;
+        let (receiver, sender) = self.net.join(id);
+        let mut stdin = child.stdin.take().unwrap();
+        spawn(move || {
+            for msg in receiver {
+                let mut line = serde_json::to_vec(&msg).unwrap();
+                line.push(b'\n');
+                if stdin.write_all(&line).is_err() {
+                    break;
+                }
+            }
+        });
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let node = id.to_owned();
+        spawn(move || {
+            for line in stdout.lines().map_while(Result::ok) {
+                match serde_json::from_str::<Msg>(&line) {
+                    Ok(msg) => {
+                        if sender.send(msg).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => eprintln!("{node} printed invalid message {line:?}: {e}"),
+                }
+            }
+        });
+        self.children.lock().insert(id.to_owned(), child);
+        Ok(())
+    }
+}
+
+impl Lifecycle for Processes {
+    fn kill(&self, node: &str) {
+        self.net.leave(node);
+        if let Some(mut child) = self.children.lock().remove(node) {
+            child.kill().ok();
+            child.wait().ok();
+        }
+    }
+
+    fn restart(&self, node: &str) {
+        if let Err(e) = self.start(node) {
+            eprintln!("cannot restart {node}: {e}");
+            return;
+        }
+        let body = json!({"type": "init", "node_id": node, "node_ids": self.nodes});
+        Client::new(&self.net, "nemesis")
+            .rpc(node, body, Duration::from_secs(10))
+            .ok();
+    }
+}
+
+impl Drop for Processes {
+    fn drop(&mut self) {
+        for (_, mut child) in std::mem::take(&mut *self.children.lock()) {
+            child.kill().ok();
+            child.wait().ok();
+        }
+    }
+}
@@ -0,0 +1,405 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+/// State-based conflict-free replicated data type
+///
+/// `merge` must be commutative, associative and idempotent so that replicas
+/// converge whatever the order, duplication or delay of the exchanged states.
+pub trait Crdt: Clone + Default {
+    /// Join `other` into this state
+    fn merge(&mut self, other: &Self);
+
+    /// Part of this state that `other` has not observed yet
+    ///
+    /// Merging the delta into `other` gives the same result as merging the
+    /// whole state, but the delta is usually much smaller.
+    fn delta(&self, other: &Self) -> Self;
+
+    /// Merge and return true if this state changed
+    fn absorb(&mut self, other: &Self) -> bool
+    where
+        Self: PartialEq,
+    {
+        let prev = self.clone();
+        self.merge(other);
+        *self != prev
+    }
+}
+
+/// Grow-only counter, one monotonic count per node
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct GCounter {
+    counts: BTreeMap<String, u64>,
+}
+
+impl GCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn increment(&mut self, node: &str, n: u64) {
+        if n > 0 {
+            *self.counts.entry(node.to_owned()).or_default() += n;
+        }
+    }
+
+    pub fn get(&self, node: &str) -> u64 {
+        self.counts.get(node).copied().unwrap_or(0)
+    }
+
+    pub fn value(&self) -> u64 {
+        self.counts.values().sum()
+    }
+}
+
+impl Crdt for GCounter {
+    fn merge(&mut self, other: &Self) {
+        for (node, count) in &other.counts {
+            let curr = self.counts.entry(node.clone()).or_default();
+            *curr = (*curr).max(*count);
+        }
+    }
+
+    fn delta(&self, other: &Self) -> Self {
+        Self {
+            counts: self
+                .counts
+                .iter()
+                .filter(|(node, count)| other.get(node) < **count)
+                .map(|(node, count)| (node.clone(), *count))
+                .collect(),
+        }
+    }
+}
+
+/// Counter supporting increments and decrements, as a pair of G-Counters
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PNCounter {
+    p: GCounter,
+    n: GCounter,
+}
+
+impl PNCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, node: &str, delta: i64) {
+        if delta >= 0 {
+            self.p.increment(node, delta as u64)
+        } else {
+            self.n.increment(node, delta.unsigned_abs())
+        }
+    }
+
+    pub fn value(&self) -> i64 {
+        self.p.value() as i64 - self.n.value() as i64
+    }
+}
+
+impl Crdt for PNCounter {
+    fn merge(&mut self, other: &Self) {
+        self.p.merge(&other.p);
+        self.n.merge(&other.n);
+    }
+
+    fn delta(&self, other: &Self) -> Self {
+        Self {
+            p: self.p.delta(&other.p),
+            n: self.n.delta(&other.n),
+        }
+    }
+}
+
+/// Grow-only set
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct GSet<T: Ord> {
+    elems: BTreeSet<T>,
+}
+
+impl<T: Ord> Default for GSet<T> {
+    fn default() -> Self {
+        Self {
+            elems: BTreeSet::new(),
+        }
+    }
+}
+
+impl<T: Ord> GSet<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert an element, returning true if it was not already present
+    pub fn insert(&mut self, elem: T) -> bool {
+        self.elems.insert(elem)
+    }
+
+    pub fn contains(&self, elem: &T) -> bool {
+        self.elems.contains(elem)
+    }
+
+    pub fn len(&self) -> usize {
+        self.elems.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elems.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.elems.iter()
+    }
+}
+
+impl<T: Ord + Clone> Crdt for GSet<T> {
+    fn merge(&mut self, other: &Self) {
+        self.elems.extend(other.elems.iter().cloned());
+    }
+
+    fn delta(&self, other: &Self) -> Self {
+        Self {
+            elems: self.elems.difference(&other.elems).cloned().collect(),
+        }
+    }
+}
+
+/// Unique tag of an add operation: the adding node and its local counter
+pub type Dot = (String, u64);
+
+/// Observed-remove set with add-wins semantic
+///
+/// Each add is tagged with a fresh dot, and a remove only tombstones the dots
+/// it has observed, so a concurrent add survives the remove.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(bound(serialize = "T: Serialize", deserialize = "T: Deserialize<'de>"))]
+pub struct ORSet<T: Ord> {
+    #[serde(with = "pairs")]
+    adds: BTreeMap<T, BTreeSet<Dot>>,
+    removed: BTreeSet<Dot>,
+    clock: GCounter,
+}
+
+impl<T: Ord> Default for ORSet<T> {
+    fn default() -> Self {
+        Self {
+            adds: BTreeMap::new(),
+            removed: BTreeSet::new(),
+            clock: GCounter::new(),
+        }
+    }
+}
+
+impl<T: Ord> ORSet<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, node: &str, elem: T) {
+        self.clock.increment(node, 1);
+        let dot = (node.to_owned(), self.clock.get(node));
+        self.adds.entry(elem).or_default().insert(dot);
+    }
+
+    /// Remove every observed occurrence of the element
+    pub fn remove(&mut self, elem: &T) {
+        if let Some(dots) = self.adds.get(elem) {
+            self.removed.extend(dots.iter().cloned());
+        }
+    }
+
+    pub fn contains(&self, elem: &T) -> bool {
+        self.adds
+            .get(elem)
+            .map(|dots| !dots.is_subset(&self.removed))
+            .unwrap_or(false)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.adds
+            .iter()
+            .filter(|(_, dots)| !dots.is_subset(&self.removed))
+            .map(|(elem, _)| elem)
+    }
+}
+
+impl<T: Ord + Clone> Crdt for ORSet<T> {
+    fn merge(&mut self, other: &Self) {
+        for (elem, dots) in &other.adds {
+            self.adds
+                .entry(elem.clone())
+                .or_default()
+                .extend(dots.iter().cloned());
+        }
+        self.removed.extend(other.removed.iter().cloned());
+        self.clock.merge(&other.clock);
+    }
+
+    fn delta(&self, other: &Self) -> Self {
+        let adds = self
+            .adds
+            .iter()
+            .filter_map(|(elem, dots)| {
+                let dots: BTreeSet<Dot> = match other.adds.get(elem) {
+                    Some(seen) => dots.difference(seen).cloned().collect(),
+                    None => dots.clone(),
+                };
+                (!dots.is_empty()).then(|| (elem.clone(), dots))
+            })
+            .collect();
+        Self {
+            adds,
+            removed: self.removed.difference(&other.removed).cloned().collect(),
+            clock: self.clock.delta(&other.clock),
+        }
+    }
+}
+
+/// Timestamp of a write, the node id breaking ties between equal clocks
+pub type Stamp = (u64, String);
+
+/// Last-writer-wins register
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LWWRegister<T> {
+    stamp: Stamp,
+    value: Option<T>,
+}
+
+impl<T> Default for LWWRegister<T> {
+    fn default() -> Self {
+        Self {
+            stamp: (0, String::new()),
+            value: None,
+        }
+    }
+}
+
+impl<T: Ord> LWWRegister<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write a value at `time`, ignored if a later write is already known
+    pub fn set(&mut self, node: &str, time: u64, value: T) {
+        let stamp = (time, node.to_owned());
+        if (&stamp, Some(&value)) > (&self.stamp, self.value.as_ref()) {
+            self.stamp = stamp;
+            self.value = Some(value);
+        }
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        self.value.as_ref()
+    }
+
+    pub fn stamp(&self) -> &Stamp {
+        &self.stamp
+    }
+}
+
+impl<T: Ord + Clone> Crdt for LWWRegister<T> {
+    fn merge(&mut self, other: &Self) {
+        // Compare values too so that two writes with the same stamp converge
+        if (&other.stamp, &other.value) > (&self.stamp, &self.value) {
+            self.stamp = other.stamp.clone();
+            self.value = other.value.clone();
+        }
+    }
+
+    fn delta(&self, other: &Self) -> Self {
+        if (&self.stamp, &self.value) > (&other.stamp, &other.value) {
+            self.clone()
+        } else {
+            Self::default()
+        }
+    }
+}
+
+/// Version vector, one logical clock per node
+pub type VClock = BTreeMap<String, u64>;
+
+fn dominated(a: &VClock, b: &VClock) -> bool {
+    a != b
+        && a.iter()
+            .all(|(node, t)| b.get(node).map(|o| t <= o).unwrap_or(false))
+}
+
+/// Multi-value register keeping every concurrent write
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct MVRegister<T: Ord> {
+    entries: BTreeSet<(VClock, T)>,
+}
+
+impl<T: Ord> Default for MVRegister<T> {
+    fn default() -> Self {
+        Self {
+            entries: BTreeSet::new(),
+        }
+    }
+}
+
+impl<T: Ord> MVRegister<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Overwrite every value observed by this replica
+    pub fn set(&mut self, node: &str, value: T) {
+        let mut clock = VClock::new();
+        for (vc, _) in &self.entries {
+            for (n, t) in vc {
+                let curr = clock.entry(n.clone()).or_default();
+                *curr = (*curr).max(*t);
+            }
+        }
+        *clock.entry(node.to_owned()).or_default() += 1;
+        self.entries = BTreeSet::from([(clock, value)]);
+    }
+
+    /// Concurrent values, a single one if there was no conflict
+    pub fn get(&self) -> impl Iterator<Item = &T> {
+        self.entries.iter().map(|(_, v)| v)
+    }
+}
+
+impl<T: Ord + Clone> Crdt for MVRegister<T> {
+    fn merge(&mut self, other: &Self) {
+        self.entries.extend(other.entries.iter().cloned());
+        let clocks: Vec<VClock> = self.entries.iter().map(|(vc, _)| vc.clone()).collect();
+        self.entries
+            .retain(|(vc, _)| !clocks.iter().any(|other| dominated(vc, other)));
+    }
+
+    fn delta(&self, other: &Self) -> Self {
+        Self {
+            entries: self.entries.difference(&other.entries).cloned().collect(),
+        }
+    }
+}
+
+/// Serialize maps as a list of pairs, JSON only supporting string keys
+mod pairs {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<K: Serialize, V: Serialize, S: Serializer>(
+        map: &BTreeMap<K, V>,
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        s.collect_seq(map.iter())
+    }
+
+    pub fn deserialize<'de, K, V, D>(d: D) -> Result<BTreeMap<K, V>, D::Error>
+    where
+        K: Deserialize<'de> + Ord,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Vec::<(K, V)>::deserialize(d).map(BTreeMap::from_iter)
+    }
+}
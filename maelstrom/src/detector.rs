@@ -0,0 +1,203 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::mpsc::{channel, Receiver, Sender},
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+use serde_json::json;
+
+use crate::Node;
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Maximum silence toward a peer before sending an explicit heartbeat
+    pub interval: Duration,
+    /// Suspicion level above which a peer is considered down
+    pub threshold: f64,
+    /// Number of inter-arrival samples kept per peer
+    pub window: usize,
+    /// Lower bound of the standard deviation, avoid suspecting on small jitter
+    pub min_std_dev: Duration,
+    /// Extra delay tolerated on top of the mean inter-arrival time
+    pub acceptable_pause: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(500),
+            threshold: 8.0,
+            window: 100,
+            min_std_dev: Duration::from_millis(100),
+            acceptable_pause: Duration::from_millis(500),
+        }
+    }
+}
+
+struct Arrivals {
+    last: Instant,
+    /// Inter-arrival times in milliseconds
+    samples: VecDeque<f64>,
+    alive: bool,
+}
+
+/// Change of a peer liveness
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub peer: String,
+    pub alive: bool,
+}
+
+/// Phi-accrual failure detector
+///
+/// Every message received from a peer counts as a heartbeat, explicit
+/// heartbeats are only sent to peers we have not sent anything to for a
+/// whole interval. The suspicion level grows with the time since the last
+/// arrival relative to the observed distribution of inter-arrival times,
+/// which adapts to the network latency instead of using a fixed timeout.
+pub struct Detector {
+    config: Config,
+    peers: Mutex<BTreeMap<String, Arrivals>>,
+    last_sent: Mutex<BTreeMap<String, Instant>>,
+    subscribers: Mutex<Vec<Sender<Change>>>,
+}
+
+impl Detector {
+    pub fn new<'a>(config: Config, peers: impl IntoIterator<Item = &'a String>) -> Self {
+        let tmp = Self {
+            config,
+            peers: Mutex::new(BTreeMap::new()),
+            last_sent: Mutex::new(BTreeMap::new()),
+            subscribers: Mutex::new(Vec::new()),
+        };
+        tmp.watch(peers);
+        tmp
+    }
+
+    /// Replace the set of monitored peers
+    pub fn watch<'a>(&self, peers: impl IntoIterator<Item = &'a String>) {
+        let now = Instant::now();
+        // Bootstrap with one expected interval so that silent peers get suspected
+        let bootstrap = self.config.interval.as_secs_f64() * 1000.;
+        let mut lock = self.peers.lock();
+        let mut prev = std::mem::take(&mut *lock);
+        for peer in peers {
+            let arrivals = prev.remove(peer).unwrap_or_else(|| Arrivals {
+                last: now,
+                samples: VecDeque::from([bootstrap]),
+                alive: true,
+            });
+            lock.insert(peer.clone(), arrivals);
+        }
+    }
+
+    /// Receive liveness changes of the monitored peers
+    pub fn subscribe(&self) -> Receiver<Change> {
+        let (sender, receiver) = channel();
+        self.subscribers.lock().push(sender);
+        receiver
+    }
+
+    pub(crate) fn heartbeat(&self, from: &str) {
+        let now = Instant::now();
+        if let Some(arrivals) = self.peers.lock().get_mut(from) {
+            let elapsed = now.duration_since(arrivals.last);
+            arrivals.last = now;
+            // Bursts of piggybacked messages would shrink the estimated interval
+            if elapsed * 2 >= self.config.interval {
+                if arrivals.samples.len() == self.config.window {
+                    arrivals.samples.pop_front();
+                }
+                arrivals.samples.push_back(elapsed.as_secs_f64() * 1000.);
+            }
+        }
+    }
+
+    pub(crate) fn sent(&self, to: &str) {
+        if let Some(last) = self.last_sent.lock().get_mut(to) {
+            *last = Instant::now();
+        }
+    }
+
+    fn phi_of(&self, arrivals: &Arrivals, now: Instant) -> f64 {
+        let n = arrivals.samples.len() as f64;
+        let mean = arrivals.samples.iter().sum::<f64>() / n;
+        let variance = arrivals
+            .samples
+            .iter()
+            .map(|s| (s - mean).powi(2))
+            .sum::<f64>()
+            / n;
+        let std_dev = variance
+            .sqrt()
+            .max(self.config.min_std_dev.as_secs_f64() * 1000.);
+        let mean = mean + self.config.acceptable_pause.as_secs_f64() * 1000.;
+        let elapsed = now.duration_since(arrivals.last).as_secs_f64() * 1000.;
+
+        // Logistic approximation of the normal cumulative distribution
+        let y = (elapsed - mean) / std_dev;
+        let e = (-y * (1.5976 + 0.070566 * y * y)).exp();
+        if elapsed > mean {
+            -(e / (1.0 + e)).log10()
+        } else {
+            -(1.0 - 1.0 / (1.0 + e)).log10()
+        }
+    }
+
+    /// Suspicion level of a peer, 0 for unknown peers
+    pub fn phi(&self, peer: &str) -> f64 {
+        self.peers
+            .lock()
+            .get(peer)
+            .map(|a| self.phi_of(a, Instant::now()))
+            .unwrap_or(0.)
+    }
+
+    /// Whether a peer is trusted, unknown peers are always trusted
+    pub fn is_alive(&self, peer: &str) -> bool {
+        self.phi(peer) < self.config.threshold
+    }
+
+    /// Send due heartbeats and notify liveness changes
+    pub(crate) fn tick(&self, node: &Node) {
+        let now = Instant::now();
+        let mut changes = Vec::new();
+        let due: Vec<String> = {
+            let peers = self.peers.lock();
+            let mut last_sent = self.last_sent.lock();
+            last_sent.retain(|peer, _| peers.contains_key(peer));
+            peers
+                .keys()
+                .filter(|peer| {
+                    let last = last_sent.entry(peer.to_string()).or_insert(now);
+                    now.duration_since(*last) >= self.config.interval
+                })
+                .cloned()
+                .collect()
+        };
+        for peer in due {
+            // Sending updates last_sent
+            node.send(peer, json!({"type": "heartbeat"}));
+        }
+        for (peer, arrivals) in self.peers.lock().iter_mut() {
+            let alive = self.phi_of(arrivals, now) < self.config.threshold;
+            if alive != arrivals.alive {
+                arrivals.alive = alive;
+                changes.push(Change {
+                    peer: peer.clone(),
+                    alive,
+                });
+            }
+        }
+        if !changes.is_empty() {
+            self.subscribers
+                .lock()
+                .retain(|s| changes.iter().all(|c| s.send(c.clone()).is_ok()));
+        }
+    }
+
+    pub(crate) fn tick_interval(&self) -> Duration {
+        self.config.interval / 4
+    }
+}
@@ -0,0 +1,558 @@
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+use serde_json::{json, Map, Value};
+
+use crate::history::{History, Op, Type};
+
+/// EDN value, as printed by Clojure and read from maelstrom stores
+#[derive(Debug, Clone, PartialEq)]
+pub enum Edn {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Char(char),
+    /// Keyword, without its leading colon
+    Keyword(String),
+    Symbol(String),
+    List(Vec<Edn>),
+    Vector(Vec<Edn>),
+    Set(Vec<Edn>),
+    Map(Vec<(Edn, Edn)>),
+    /// Tagged element such as `#inst "..."` or a `#jepsen.history.Op{...}` record
+    Tagged(String, Box<Edn>),
+}
+
+impl Edn {
+    pub fn keyword(name: &str) -> Self {
+        Edn::Keyword(name.to_owned())
+    }
+
+    /// Value under a keyword key of a map, looking through tags
+    pub fn get(&self, key: &str) -> Option<&Edn> {
+        match self {
+            Edn::Map(entries) => entries
+                .iter()
+                .find(|(k, _)| matches!(k, Edn::Keyword(k) if k == key))
+                .map(|(_, v)| v),
+            Edn::Tagged(_, inner) => inner.get(key),
+            _ => None,
+        }
+    }
+
+    /// JSON equivalent, keywords and symbols becoming their name, tags being
+    /// dropped, and map keys other than strings and keywords being printed
+    pub fn to_json(&self) -> Value {
+        match self {
+            Edn::Nil => Value::Null,
+            Edn::Bool(b) => json!(b),
+            Edn::Int(i) => json!(i),
+            Edn::Float(f) => json!(f),
+            Edn::Str(s) | Edn::Keyword(s) | Edn::Symbol(s) => json!(s),
+            Edn::Char(c) => json!(c.to_string()),
+            Edn::List(items) | Edn::Vector(items) | Edn::Set(items) => {
+                Value::Array(items.iter().map(Edn::to_json).collect())
+            }
+            Edn::Map(entries) => {
+                let map: Map<String, Value> = entries
+                    .iter()
+                    .map(|(k, v)| {
+                        let key = match k {
+                            Edn::Str(s) | Edn::Keyword(s) | Edn::Symbol(s) => s.clone(),
+                            other => other.to_string(),
+                        };
+                        (key, v.to_json())
+                    })
+                    .collect();
+                Value::Object(map)
+            }
+            Edn::Tagged(_, inner) => inner.to_json(),
+        }
+    }
+
+    /// EDN equivalent of JSON, object keys becoming keywords
+    pub fn from_json(value: &Value) -> Self {
+        match value {
+            Value::Null => Edn::Nil,
+            Value::Bool(b) => Edn::Bool(*b),
+            Value::Number(n) => match n.as_i64() {
+                Some(i) => Edn::Int(i),
+                None => Edn::Float(n.as_f64().unwrap()),
+            },
+            Value::String(s) => Edn::Str(s.clone()),
+            Value::Array(items) => Edn::Vector(items.iter().map(Edn::from_json).collect()),
+            Value::Object(map) => Edn::Map(
+                map.iter()
+                    .map(|(k, v)| (Edn::keyword(k), Edn::from_json(v)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+fn write_seq(f: &mut fmt::Formatter<'_>, open: &str, items: &[Edn], close: &str) -> fmt::Result {
+    f.write_str(open)?;
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            f.write_str(" ")?;
+        }
+        write!(f, "{item}")?;
+    }
+    f.write_str(close)
+}
+
+impl Display for Edn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Edn::Nil => f.write_str("nil"),
+            Edn::Bool(b) => write!(f, "{b}"),
+            Edn::Int(i) => write!(f, "{i}"),
+            Edn::Float(x) if x.is_nan() => f.write_str("##NaN"),
+            Edn::Float(x) if x.is_infinite() => {
+                f.write_str(if *x > 0. { "##Inf" } else { "##-Inf" })
+            }
+            Edn::Float(x) if x.fract() == 0. && x.abs() < 1e16 => write!(f, "{x:.1}"),
+            Edn::Float(x) => write!(f, "{x}"),
+            Edn::Str(s) => {
+                f.write_str("\"")?;
+                for c in s.chars() {
+                    match c {
+                        '"' => f.write_str("\\\"")?,
+                        '\\' => f.write_str("\\\\")?,
+                        '\n' => f.write_str("\\n")?,
+                        '\t' => f.write_str("\\t")?,
+                        '\r' => f.write_str("\\r")?,
+                        c => write!(f, "{c}")?,
+                    }
+                }
+                f.write_str("\"")
+            }
+            Edn::Char(c) => match c {
+                '\n' => f.write_str("\\newline"),
+                ' ' => f.write_str("\\space"),
+                '\t' => f.write_str("\\tab"),
+                '\r' => f.write_str("\\return"),
+                c => write!(f, "\\{c}"),
+            },
+            Edn::Keyword(k) => write!(f, ":{k}"),
+            Edn::Symbol(s) => f.write_str(s),
+            Edn::List(items) => write_seq(f, "(", items, ")"),
+            Edn::Vector(items) => write_seq(f, "[", items, "]"),
+            Edn::Set(items) => write_seq(f, "#{", items, "}"),
+            Edn::Map(entries) => {
+                f.write_str("{")?;
+                for (i, (k, v)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{k} {v}")?;
+                }
+                f.write_str("}")
+            }
+            Edn::Tagged(tag, inner) => match **inner {
+                // Records print their map right after the tag
+                Edn::Map(_) => write!(f, "#{tag}{inner}"),
+                _ => write!(f, "#{tag} {inner}"),
+            },
+        }
+    }
+}
+
+impl FromStr for Edn {
+    type Err = String;
+
+    /// Read exactly one element
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut items = parse(s)?;
+        match items.len() {
+            1 => Ok(items.pop().unwrap()),
+            n => Err(format!("expected one element, found {n}")),
+        }
+    }
+}
+
+/// Read every top-level element of a text, as in a `history.edn` holding
+/// one operation per line
+pub fn parse(s: &str) -> Result<Vec<Edn>, String> {
+    let mut reader = Reader { s, pos: 0 };
+    let mut items = Vec::new();
+    while let Some(item) = reader.next()? {
+        items.push(item);
+    }
+    Ok(items)
+}
+
+struct Reader<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn error(&self, msg: impl Display) -> String {
+        let line = self.s[..self.pos].matches('\n').count() + 1;
+        format!("{msg} at line {line}")
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.s[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    /// Skip whitespace, commas, comments and `#_` discarded elements
+    fn skip_space(&mut self) -> Result<(), String> {
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || c == ',' {
+                self.bump();
+            } else if c == ';' {
+                while !matches!(self.bump(), None | Some('\n')) {}
+            } else if self.s[self.pos..].starts_with("#_") {
+                self.pos += 2;
+                self.next()?.ok_or_else(|| self.error("nothing after #_"))?;
+            } else {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Next element, None at the end of the input
+    fn next(&mut self) -> Result<Option<Edn>, String> {
+        self.skip_space()?;
+        let Some(c) = self.peek() else {
+            return Ok(None);
+        };
+        let item = match c {
+            '(' | '[' | '{' => {
+                self.bump();
+                let close = match c {
+                    '(' => ')',
+                    '[' => ']',
+                    _ => '}',
+                };
+                let items = self.until(close)?;
+                match c {
+                    '(' => Edn::List(items),
+                    '[' => Edn::Vector(items),
+                    _ => self.map(items)?,
+                }
+            }
+            ')' | ']' | '}' => return Err(self.error(format!("unexpected {c:?}"))),
+            '"' => self.string()?,
+            '\\' => self.char()?,
+            ':' => {
+                self.bump();
+                Edn::Keyword(self.token())
+            }
+            '#' => self.dispatch()?,
+            _ => self.atom()?,
+        };
+        Ok(Some(item))
+    }
+
+    fn until(&mut self, close: char) -> Result<Vec<Edn>, String> {
+        let mut items = Vec::new();
+        loop {
+            self.skip_space()?;
+            if self.peek() == Some(close) {
+                self.bump();
+                return Ok(items);
+            }
+            match self.next()? {
+                Some(item) => items.push(item),
+                None => return Err(self.error(format!("missing {close:?}"))),
+            }
+        }
+    }
+
+    fn map(&self, items: Vec<Edn>) -> Result<Edn, String> {
+        if items.len() % 2 == 1 {
+            return Err(self.error("map with an odd number of forms"));
+        }
+        let mut items = items.into_iter();
+        let mut entries = Vec::new();
+        while let (Some(k), Some(v)) = (items.next(), items.next()) {
+            entries.push((k, v));
+        }
+        Ok(Edn::Map(entries))
+    }
+
+    fn token(&mut self) -> String {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || matches!(c, ',' | '(' | ')' | '[' | ']' | '{' | '}' | '"' | ';')
+            {
+                break;
+            }
+            self.bump();
+        }
+        self.s[start..self.pos].to_owned()
+    }
+
+    fn string(&mut self) -> Result<Edn, String> {
+        self.bump();
+        let mut s = String::new();
+        loop {
+            match self.bump() {
+                None => return Err(self.error("unterminated string")),
+                Some('"') => return Ok(Edn::Str(s)),
+                Some('\\') => match self.bump() {
+                    Some('n') => s.push('\n'),
+                    Some('t') => s.push('\t'),
+                    Some('r') => s.push('\r'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('u') => {
+                        let hex = self.s.get(self.pos..self.pos + 4).unwrap_or_default();
+                        let code = u32::from_str_radix(hex, 16)
+                            .map_err(|_| self.error("invalid unicode escape"))?;
+                        self.pos += 4;
+                        s.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                    }
+                    Some(c) => s.push(c),
+                    None => return Err(self.error("unterminated string")),
+                },
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn char(&mut self) -> Result<Edn, String> {
+        self.bump();
+        // A lone delimiter such as `\(` is a character too
+        let first = self.bump().ok_or_else(|| self.error("missing character"))?;
+        let rest = self.token();
+        let c = match (first, rest.as_str()) {
+            (c, "") => c,
+            ('n', "ewline") => '\n',
+            ('s', "pace") => ' ',
+            ('t', "ab") => '\t',
+            ('r', "eturn") => '\r',
+            ('u', hex) if hex.len() == 4 => u32::from_str_radix(hex, 16)
+                .ok()
+                .and_then(char::from_u32)
+                .ok_or_else(|| self.error("invalid unicode character"))?,
+            _ => return Err(self.error(format!("unknown character \\{first}{rest}"))),
+        };
+        Ok(Edn::Char(c))
+    }
+
+    fn dispatch(&mut self) -> Result<Edn, String> {
+        self.bump();
+        match self.peek() {
+            Some('{') => {
+                self.bump();
+                Ok(Edn::Set(self.until('}')?))
+            }
+            Some('#') => {
+                self.bump();
+                match self.token().as_str() {
+                    "Inf" => Ok(Edn::Float(f64::INFINITY)),
+                    "-Inf" => Ok(Edn::Float(f64::NEG_INFINITY)),
+                    "NaN" => Ok(Edn::Float(f64::NAN)),
+                    other => Err(self.error(format!("unknown symbolic value ##{other}"))),
+                }
+            }
+            _ => {
+                let tag = self.token();
+                if tag.is_empty() {
+                    return Err(self.error("missing tag after #"));
+                }
+                let inner = self
+                    .next()?
+                    .ok_or_else(|| self.error("nothing after tag"))?;
+                Ok(Edn::Tagged(tag, Box::new(inner)))
+            }
+        }
+    }
+
+    fn atom(&mut self) -> Result<Edn, String> {
+        let token = self.token();
+        if token.is_empty() {
+            return Err(self.error(format!("unexpected {:?}", self.peek())));
+        }
+        Ok(match token.as_str() {
+            "nil" => Edn::Nil,
+            "true" => Edn::Bool(true),
+            "false" => Edn::Bool(false),
+            t if t.starts_with(|c: char| c.is_ascii_digit())
+                || (t.len() > 1
+                    && t.starts_with(['-', '+'])
+                    && t[1..].starts_with(|c: char| c.is_ascii_digit())) =>
+            {
+                number(t).ok_or_else(|| self.error(format!("invalid number {t}")))?
+            }
+            t => Edn::Symbol(t.to_owned()),
+        })
+    }
+}
+
+/// Integer, bigint, float, bigdecimal or ratio, the latter two as floats
+fn number(t: &str) -> Option<Edn> {
+    let t = t.strip_suffix('N').unwrap_or(t);
+    if let Ok(i) = t.parse() {
+        return Some(Edn::Int(i));
+    }
+    if let Some((n, d)) = t.split_once('/') {
+        return Some(Edn::Float(n.parse::<f64>().ok()? / d.parse::<f64>().ok()?));
+    }
+    t.strip_suffix('M')
+        .unwrap_or(t)
+        .parse()
+        .ok()
+        .map(Edn::Float)
+}
+
+/// Kafka values in jepsen are single micro-op transactions
+fn kafka_import(f: &str, ty: Type, value: &Edn) -> Option<Value> {
+    let Some(Edn::Vector(mop)) = (match value {
+        Edn::Vector(mops) => mops.first(),
+        _ => None,
+    }) else {
+        return None;
+    };
+    let key = |k: &Edn| match k {
+        Edn::Str(s) => json!(s),
+        other => json!(other.to_string()),
+    };
+    match (f, ty, mop.as_slice()) {
+        ("send", Type::Ok, [_, k, Edn::Vector(ov)]) if ov.len() == 2 => {
+            Some(json!([key(k), ov[1].to_json(), ov[0].to_json()]))
+        }
+        ("send", _, [_, k, v]) => Some(json!([key(k), v.to_json()])),
+        ("poll", Type::Ok, [_, Edn::Map(msgs)]) => Some(Value::Object(
+            msgs.iter()
+                .map(|(k, v)| (key(k).as_str().unwrap().to_owned(), v.to_json()))
+                .collect(),
+        )),
+        ("poll", _, _) => Some(Value::Null),
+        _ => None,
+    }
+}
+
+fn kafka_export(f: &str, ty: Type, value: &Value) -> Option<Edn> {
+    // Keys are integers in jepsen, strings on the wire
+    let key = |k: &Value| match k.as_str().and_then(|k| k.parse().ok()) {
+        Some(k) => Edn::Int(k),
+        None => Edn::from_json(k),
+    };
+    let mop = match (f, ty) {
+        ("send", Type::Ok) if value.as_array().is_some_and(|v| v.len() == 3) => vec![
+            Edn::keyword("send"),
+            key(&value[0]),
+            Edn::Vector(vec![Edn::from_json(&value[2]), Edn::from_json(&value[1])]),
+        ],
+        ("send", _) => vec![
+            Edn::keyword("send"),
+            key(&value[0]),
+            Edn::from_json(&value[1]),
+        ],
+        ("poll", Type::Ok) => {
+            let msgs = value.as_object()?;
+            let msgs = msgs
+                .iter()
+                .map(|(k, v)| (key(&json!(k)), Edn::from_json(v)))
+                .collect();
+            vec![Edn::keyword("poll"), Edn::Map(msgs)]
+        }
+        ("poll", _) => vec![Edn::keyword("poll")],
+        _ => return None,
+    };
+    Some(Edn::Vector(vec![Edn::Vector(mop)]))
+}
+
+/// Txn micro-ops start with a keyword function
+fn txn_export(value: &Value) -> Option<Edn> {
+    let mops = value.as_array()?;
+    let mops = mops.iter().map(|mop| match mop.as_array() {
+        Some(mop) if mop.first().is_some_and(Value::is_string) => {
+            let mut items = vec![Edn::keyword(mop[0].as_str().unwrap())];
+            items.extend(mop[1..].iter().map(Edn::from_json));
+            Edn::Vector(items)
+        }
+        _ => Edn::from_json(mop),
+    });
+    Some(Edn::Vector(mops.collect()))
+}
+
+impl History {
+    /// Client operations of a jepsen history, such as maelstrom's
+    /// `history.edn`, nemesis operations being skipped
+    ///
+    /// Values are converted to those recorded by `generator::run`, e.g. kafka
+    /// micro-ops `[[:send k v]]` to `[k, v]`.
+    pub fn from_edn(s: &str) -> Result<Self, String> {
+        let mut items = parse(s)?;
+        // Either one operation per line or a single vector of them
+        if let [Edn::Vector(ops)] = items.as_mut_slice() {
+            items = std::mem::take(ops);
+        }
+        let mut ops = Vec::new();
+        for item in &items {
+            let Some(Edn::Int(process)) = item.get("process") else {
+                continue;
+            };
+            let keyword = |key: &str| match item.get(key) {
+                Some(Edn::Keyword(k) | Edn::Str(k)) => Ok(k.clone()),
+                _ => Err(format!("operation without {key}: {item}")),
+            };
+            let ty: Type = serde_json::from_value(json!(keyword("type")?))
+                .map_err(|_| format!("unknown type in {item}"))?;
+            let f = keyword("f")?;
+            let raw = item.get("value").unwrap_or(&Edn::Nil);
+            let value = kafka_import(&f, ty, raw).unwrap_or_else(|| raw.to_json());
+            let time = match item.get("time") {
+                Some(Edn::Int(t)) => *t as u64,
+                _ => 0,
+            };
+            ops.push(Op {
+                index: ops.len(),
+                time,
+                process: *process as u64,
+                ty,
+                f,
+                value,
+                r#final: item.get("final?") == Some(&Edn::Bool(true)),
+            });
+        }
+        Ok(History::from_ops(ops))
+    }
+
+    /// Jepsen history with one operation per line, for maelstrom, elle or
+    /// knossos to check
+    pub fn to_edn(&self) -> String {
+        let mut out = String::new();
+        for op in &self.ops {
+            let ty = serde_json::to_value(op.ty).unwrap();
+            let value = match op.f.as_str() {
+                "txn" => txn_export(&op.value),
+                f => kafka_export(f, op.ty, &op.value),
+            };
+            let mut entries = vec![
+                (Edn::keyword("index"), Edn::Int(op.index as i64)),
+                (Edn::keyword("time"), Edn::Int(op.time as i64)),
+                (Edn::keyword("type"), Edn::keyword(ty.as_str().unwrap())),
+                (Edn::keyword("process"), Edn::Int(op.process as i64)),
+                (Edn::keyword("f"), Edn::keyword(&op.f)),
+                (
+                    Edn::keyword("value"),
+                    value.unwrap_or_else(|| Edn::from_json(&op.value)),
+                ),
+            ];
+            if op.r#final {
+                entries.push((Edn::keyword("final?"), Edn::Bool(true)));
+            }
+            out += &Edn::Map(entries).to_string();
+            out.push('\n');
+        }
+        out
+    }
+}
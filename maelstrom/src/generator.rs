@@ -0,0 +1,422 @@
+use std::{
+    collections::BTreeMap,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering::SeqCst},
+        mpsc::{Receiver, SyncSender},
+    },
+    thread::{scope, sleep},
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    history::{History, Type},
+    net::Net,
+    topology::Topology,
+    Err, Msg,
+};
+
+/// Client workload, named as maelstrom `-w`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Workload {
+    Echo,
+    UniqueIds,
+    Broadcast,
+    PnCounter,
+    Kafka,
+    TxnRwRegister,
+    TxnListAppend,
+    LinKv,
+}
+
+impl FromStr for Workload {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "echo" => Workload::Echo,
+            "unique-ids" => Workload::UniqueIds,
+            "broadcast" => Workload::Broadcast,
+            "pn-counter" => Workload::PnCounter,
+            "kafka" => Workload::Kafka,
+            "txn-rw-register" => Workload::TxnRwRegister,
+            "txn-list-append" => Workload::TxnListAppend,
+            "lin-kv" => Workload::LinKv,
+            _ => return Err(format!("unknown workload {s}")),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub workload: Workload,
+    /// Requests per second, across all clients
+    pub rate: f64,
+    /// Number of concurrent clients, bound to the nodes round-robin
+    pub concurrency: usize,
+    pub time_limit: Duration,
+    /// Keys in use at any time for kafka, lin-kv and the txn workloads
+    pub key_count: usize,
+    pub max_txn_length: usize,
+    /// Writes before a txn key is retired for a fresh one
+    pub max_writes_per_key: u64,
+    /// Quiet period before the final reads of broadcast and pn-counter
+    pub recovery: Duration,
+    /// Client request timeout, after which the outcome is indeterminate
+    pub timeout: Duration,
+    /// Broadcast topology sent to the nodes, as maelstrom `--topology`
+    pub topology: fn(&[String]) -> Topology,
+    pub seed: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            workload: Workload::Echo,
+            rate: 5.,
+            concurrency: 5,
+            time_limit: Duration::from_secs(10),
+            key_count: 5,
+            max_txn_length: 4,
+            max_writes_per_key: 16,
+            recovery: Duration::from_secs(2),
+            timeout: Duration::from_secs(1),
+            topology: Topology::grid,
+            seed: 0,
+        }
+    }
+}
+
+/// Network participant issuing one request at a time
+pub struct Client {
+    pub id: String,
+    receiver: Receiver<Msg>,
+    sender: SyncSender<Msg>,
+    next_id: u64,
+}
+
+impl Client {
+    pub fn new(net: &Net, id: &str) -> Self {
+        let (receiver, sender) = net.join(id);
+        Self {
+            id: id.to_owned(),
+            receiver,
+            sender,
+            next_id: 0,
+        }
+    }
+
+    /// Send a request and wait for its reply body, late replies to previous
+    /// requests being discarded
+    pub fn rpc(&mut self, dest: &str, mut body: Value, timeout: Duration) -> Result<Value, Err> {
+        self.next_id += 1;
+        body["msg_id"] = self.next_id.into();
+        let msg = Msg {
+            src: self.id.clone(),
+            dest: dest.to_owned(),
+            body,
+        };
+        self.sender.send(msg).map_err(|_| Err::Crash)?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            let msg = self.receiver.recv_timeout(left).map_err(|_| Err::Timeout)?;
+            if msg.body["in_reply_to"] != self.next_id {
+                continue;
+            }
+            if msg.body["type"] == "error" {
+                let code = msg.body["code"].as_u64().unwrap_or_default();
+                // Unknown codes are indefinite
+                return Err(Err::try_from(code).unwrap_or(Err::Crash));
+            }
+            return Ok(msg.body);
+        }
+    }
+}
+
+/// Send `init` to every node and wait for them to be ready
+///
+/// As with maelstrom, every node is sent its `init` at once, rather than one
+/// after the other, so that nodes rarely hear from their peers first.
+pub fn init(net: &Net, nodes: &[String]) -> Result<(), Err> {
+    scope(|s| {
+        let inits: Vec<_> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| {
+                s.spawn(move || {
+                    let mut client = Client::new(net, &format!("init{i}"));
+                    let body = json!({"type": "init", "node_id": node, "node_ids": nodes});
+                    client.rpc(node, body, Duration::from_secs(10)).map(|_| ())
+                })
+            })
+            .collect();
+        inits.into_iter().try_for_each(|init| init.join().unwrap())
+    })
+}
+
+/// Request of a replayable client script
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Request {
+    pub client: usize,
+    /// Milliseconds since the start of the run, the client sending it as soon
+    /// as it is free past that time
+    pub at: u64,
+    pub f: String,
+    /// Invocation value, null for kafka polls and commits which use the
+    /// offsets consumed so far by the client
+    pub value: Value,
+}
+
+/// Values shared by all clients
+struct Shared {
+    /// Next broadcast message or kafka record
+    counter: u64,
+    /// Txn keys in use, with their next write
+    keys: Vec<(u64, u64)>,
+    next_key: u64,
+}
+
+/// Client state, one per process
+struct Process {
+    id: u64,
+    node: String,
+    /// Next kafka offset to poll for each key
+    offsets: BTreeMap<String, u64>,
+}
+
+impl Config {
+    fn invoke(&self, shared: &mut Shared, rng: &fastrand::Rng) -> (&'static str, Value) {
+        let key = || rng.u64(..self.key_count.max(1) as u64);
+        match self.workload {
+            Workload::Echo => ("echo", json!(format!("Please echo {}", rng.u32(..)))),
+            Workload::UniqueIds => ("generate", Value::Null),
+            Workload::Broadcast if rng.bool() => ("broadcast", json!(next(&mut shared.counter))),
+            Workload::PnCounter if rng.bool() => ("add", json!(rng.i64(-5..=5))),
+            Workload::Broadcast | Workload::PnCounter => ("read", Value::Null),
+            Workload::Kafka => match rng.u8(..10) {
+                0..=4 => (
+                    "send",
+                    json!([key().to_string(), next(&mut shared.counter)]),
+                ),
+                5..=7 => ("poll", Value::Null),
+                8 => ("commit_offsets", Value::Null),
+                _ => {
+                    let keys: Vec<String> = (0..self.key_count).map(|k| k.to_string()).collect();
+                    ("list_committed_offsets", json!(keys))
+                }
+            },
+            Workload::LinKv => {
+                let (key, value) = (key(), rng.u64(..5));
+                match rng.u8(..3) {
+                    0 => ("read", json!([key, null])),
+                    1 => ("write", json!([key, value])),
+                    _ => ("cas", json!([key, [rng.u64(..5), value]])),
+                }
+            }
+            Workload::TxnRwRegister | Workload::TxnListAppend => {
+                let write = match self.workload {
+                    Workload::TxnRwRegister => "w",
+                    _ => "append",
+                };
+                let len = rng.usize(1..=self.max_txn_length.max(1));
+                let txn: Vec<Value> = (0..len)
+                    .map(|_| {
+                        let i = rng.usize(..shared.keys.len());
+                        let (key, value) = shared.keys[i];
+                        if rng.bool() {
+                            return json!(["r", key, null]);
+                        }
+                        shared.keys[i].1 += 1;
+                        if value >= self.max_writes_per_key {
+                            // Retire the key so version orders stay short
+                            shared.keys[i] = (next(&mut shared.next_key), 1);
+                        }
+                        json!([write, key, value])
+                    })
+                    .collect();
+                ("txn", json!(txn))
+            }
+        }
+    }
+
+    /// Value to record and request body of an invocation
+    fn request(&self, f: &str, value: &Value, p: &Process) -> (Value, Value) {
+        let value = match f {
+            "poll" if value.is_null() => json!((0..self.key_count)
+                .map(|k| {
+                    let k = k.to_string();
+                    let offset = p.offsets.get(&k).copied().unwrap_or_default();
+                    (k, offset)
+                })
+                .collect::<BTreeMap<String, u64>>()),
+            "commit_offsets" if value.is_null() => json!(p.offsets),
+            _ => value.clone(),
+        };
+        let body = match f {
+            "echo" => json!({"type": "echo", "echo": value}),
+            "broadcast" => json!({"type": "broadcast", "message": value}),
+            "add" => json!({"type": "add", "delta": value}),
+            "read" if value.is_array() => json!({"type": "read", "key": value[0]}),
+            "write" => json!({"type": "write", "key": value[0], "value": value[1]}),
+            "cas" => {
+                json!({"type": "cas", "key": value[0], "from": value[1][0], "to": value[1][1]})
+            }
+            "send" => json!({"type": "send", "key": value[0], "msg": value[1]}),
+            "poll" | "commit_offsets" => json!({"type": f, "offsets": value}),
+            "list_committed_offsets" => json!({"type": f, "keys": value}),
+            "txn" => json!({"type": "txn", "txn": value}),
+            _ => json!({"type": f}),
+        };
+        (value, body)
+    }
+}
+
+/// Completion value of a successful operation
+fn complete(f: &str, invoke: &Value, reply: &Value) -> Value {
+    match f {
+        "echo" => reply["echo"].clone(),
+        "generate" => reply["id"].clone(),
+        "read" if invoke.is_array() => json!([invoke[0], reply["value"]]),
+        "read" => reply.get("messages").unwrap_or(&reply["value"]).clone(),
+        "send" => json!([invoke[0], invoke[1], reply["offset"]]),
+        "poll" => reply["msgs"].clone(),
+        "list_committed_offsets" => reply["offsets"].clone(),
+        "txn" => reply["txn"].clone(),
+        _ => invoke.clone(),
+    }
+}
+
+fn next(counter: &mut u64) -> u64 {
+    *counter += 1;
+    *counter - 1
+}
+
+/// Requests of a run, staggered so that all clients together approach `rate`
+pub fn script(config: &Config) -> Vec<Request> {
+    let stagger = config.concurrency as f64 * 2. / config.rate;
+    let limit = config.time_limit.as_secs_f64();
+    let mut times = Vec::new();
+    for c in 0..config.concurrency {
+        let rng = fastrand::Rng::with_seed(config.seed.wrapping_add(c as u64));
+        let mut t = rng.f64() * stagger;
+        while t < limit {
+            times.push(((t * 1e3) as u64, c));
+            t += rng.f64() * stagger;
+        }
+    }
+    times.sort_unstable();
+
+    // Values are drawn in time order so that txn keys retire in order
+    let rng = fastrand::Rng::with_seed(config.seed);
+    let mut shared = Shared {
+        counter: 0,
+        keys: (0..config.key_count.max(1) as u64)
+            .map(|k| (k, 1))
+            .collect(),
+        next_key: config.key_count.max(1) as u64,
+    };
+    times
+        .into_iter()
+        .map(|(at, client)| {
+            let (f, value) = config.invoke(&mut shared, &rng);
+            Request {
+                client,
+                at,
+                f: f.to_owned(),
+                value,
+            }
+        })
+        .collect()
+}
+
+/// Run a workload against initialized nodes and record its history
+pub fn run(net: &Net, nodes: &[String], config: &Config) -> History {
+    run_script(net, nodes, config, &script(config))
+}
+
+/// Replay client requests against initialized nodes and record the history
+///
+/// Each client sends its requests in order to the node it is bound to. A
+/// client whose request times out or crashes is replaced by a new process,
+/// as in Jepsen.
+pub fn run_script(net: &Net, nodes: &[String], config: &Config, requests: &[Request]) -> History {
+    let history = Mutex::new(History::since(net.start()));
+    let clients = requests.iter().map(|r| r.client + 1).max().unwrap_or(0);
+    let processes = AtomicU64::new(clients as u64);
+    let mut setup = Client::new(net, "c0");
+    if config.workload == Workload::Broadcast {
+        let topology = (config.topology)(nodes);
+        for node in nodes {
+            let body = json!({"type": "topology", "topology": topology});
+            setup.rpc(node, body, config.timeout).ok();
+        }
+    }
+
+    let start = Instant::now();
+    scope(|s| {
+        for c in 0..clients {
+            let (history, processes) = (&history, &processes);
+            s.spawn(move || {
+                let mut client = Client::new(net, &format!("c{}", c + 1));
+                let mut p = Process {
+                    id: c as u64,
+                    node: nodes[c % nodes.len()].clone(),
+                    offsets: BTreeMap::new(),
+                };
+                for request in requests.iter().filter(|r| r.client == c) {
+                    let at = start + Duration::from_millis(request.at);
+                    sleep(at.saturating_duration_since(Instant::now()));
+                    let f = request.f.as_str();
+                    let (value, body) = config.request(f, &request.value, &p);
+                    history.lock().push(p.id, Type::Invoke, f, value.clone());
+                    match client.rpc(&p.node, body, config.timeout) {
+                        Ok(reply) => {
+                            let value = complete(f, &value, &reply);
+                            if f == "poll" {
+                                for (k, msgs) in value.as_object().into_iter().flatten() {
+                                    if let Some(last) = msgs.as_array().and_then(|m| m.last()) {
+                                        p.offsets.insert(k.clone(), last[0].as_u64().unwrap() + 1);
+                                    }
+                                }
+                            }
+                            history.lock().push(p.id, Type::Ok, f, value);
+                        }
+                        Err(e) if e.is_definite() => {
+                            history.lock().push(p.id, Type::Fail, f, value);
+                        }
+                        Err(_) => {
+                            history.lock().push(p.id, Type::Info, f, value);
+                            p.id = processes.fetch_add(1, SeqCst);
+                        }
+                    }
+                }
+            });
+        }
+    });
+
+    if matches!(config.workload, Workload::Broadcast | Workload::PnCounter) {
+        sleep(config.recovery);
+        for node in nodes {
+            let process = processes.fetch_add(1, SeqCst);
+            history
+                .lock()
+                .push_final(process, Type::Invoke, "read", Value::Null);
+            let ty = match setup.rpc(node, json!({"type": "read"}), config.timeout) {
+                Ok(reply) => {
+                    let value = complete("read", &Value::Null, &reply);
+                    history.lock().push_final(process, Type::Ok, "read", value);
+                    continue;
+                }
+                Err(e) if e.is_definite() => Type::Fail,
+                Err(_) => Type::Info,
+            };
+            history.lock().push_final(process, ty, "read", Value::Null);
+        }
+    }
+    history.into_inner()
+}
@@ -0,0 +1,257 @@
+use std::{
+    collections::BTreeMap,
+    thread::{sleep, Scope},
+    time::Duration,
+};
+
+use parking_lot::{Mutex, MutexGuard, RwLock};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+
+use crate::{crdt::Crdt, fnv1a, Err, Msg, Node};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Send our new updates to the peer
+    Push,
+    /// Ask the peer for the updates we miss
+    Pull,
+    /// Both in a single round trip
+    PushPull,
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub mode: Mode,
+    /// Number of peers contacted each round
+    pub fanout: usize,
+    /// Delay between two rounds
+    pub interval: Duration,
+    /// Delay between two digest exchanges, None disables anti-entropy
+    pub anti_entropy: Option<Duration>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            mode: Mode::PushPull,
+            fanout: 3,
+            interval: Duration::from_millis(200),
+            anti_entropy: Some(Duration::from_secs(2)),
+        }
+    }
+}
+
+/// Replicate a mergeable state by periodic gossip rounds
+///
+/// Rounds only transmit the delta between our state and what we know the peer
+/// has observed. A slower anti-entropy loop compares state digests with a
+/// random peer and exchanges full states on mismatch, repairing lost deltas,
+/// healed partitions and restarted peers.
+///
+/// A delta sent in reply to a pull only counts as observed once the peer
+/// acknowledges it in its next request, so a lost reply is sent again even
+/// without anti-entropy.
+///
+/// The owner must route `gossip` and `gossip_sync` messages to `on_gossip` and
+/// `on_sync`.
+pub struct Gossip<S> {
+    config: Config,
+    state: Mutex<S>,
+    /// Last state each peer is known to have observed
+    seen: Mutex<BTreeMap<String, S>>,
+    /// Peers to gossip with, all the other nodes if unset
+    peers: RwLock<Option<Vec<String>>>,
+    /// Delta last sent in reply to each peer, with its digest, until acknowledged
+    unacked: Mutex<BTreeMap<String, (u64, S)>>,
+    /// Digest of the delta last received from each peer, to acknowledge
+    acks: Mutex<BTreeMap<String, u64>>,
+}
+
+impl<S> Gossip<S>
+where
+    S: Crdt + PartialEq + Serialize + DeserializeOwned + Send + Sync,
+{
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            state: Mutex::new(S::default()),
+            seen: Mutex::new(BTreeMap::new()),
+            peers: RwLock::new(None),
+            unacked: Mutex::new(BTreeMap::new()),
+            acks: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Local replica, to read or update
+    pub fn state(&self) -> MutexGuard<'_, S> {
+        self.state.lock()
+    }
+
+    pub fn set_peers(&self, peers: Vec<String>) {
+        *self.peers.write() = Some(peers);
+    }
+
+    /// Spawn the gossip and anti-entropy loops, running until `node.run` returns
+    pub fn start<'a, 'b>(&'b self, s: &'b Scope<'b, 'a>, node: &'a Node) {
+        s.spawn(move || {
+            while !node.is_closed() {
+                sleep(self.config.interval);
+                self.round(s, node);
+            }
+        });
+        if let Some(interval) = self.config.anti_entropy {
+            s.spawn(move || {
+                while !node.is_closed() {
+                    sleep(interval);
+                    if let Some(peer) = self.pick(node, 1).pop() {
+                        self.sync(node, peer);
+                    }
+                }
+            });
+        }
+    }
+
+    fn pick(&self, node: &Node, n: usize) -> Vec<String> {
+        let mut peers = match &*self.peers.read() {
+            Some(peers) => peers.clone(),
+            None => node.other_ids().cloned().collect(),
+        };
+        // Don't waste rounds on peers suspected to be down
+        peers.retain(|peer| node.is_alive(peer));
+        fastrand::shuffle(&mut peers);
+        peers.truncate(n);
+        peers
+    }
+
+    fn digest(state: &S) -> u64 {
+        fnv1a(&serde_json::to_vec(state).unwrap())
+    }
+
+    /// Delta of our state unknown to a peer
+    fn delta_for(&self, peer: &str) -> S {
+        let state = self.state.lock();
+        match self.seen.lock().get(peer) {
+            Some(seen) => state.delta(seen),
+            None => state.clone(),
+        }
+    }
+
+    fn observed(&self, peer: &str, state: &S) {
+        self.seen
+            .lock()
+            .entry(peer.to_owned())
+            .or_default()
+            .merge(state);
+    }
+
+    /// Run one gossip round with `fanout` random peers
+    pub fn round<'a, 'b>(&'b self, s: &'b Scope<'b, 'a>, node: &'a Node) {
+        for peer in self.pick(node, self.config.fanout) {
+            let push = (self.config.mode != Mode::Pull)
+                .then(|| self.delta_for(&peer))
+                .filter(|delta| *delta != S::default());
+            let digest = (self.config.mode != Mode::Push).then(|| Self::digest(&self.state()));
+            if push.is_none() && digest.is_none() {
+                continue;
+            }
+            s.spawn(move || {
+                let ack = self.acks.lock().remove(&peer);
+                let body = json!({
+                    "type": "gossip",
+                    "state": push,
+                    "digest": digest,
+                    "ack": ack
+                });
+                if let Ok(mut res) = node.rpc(peer.clone(), body) {
+                    if let Some(push) = &push {
+                        self.observed(&peer, push);
+                    }
+                    if let Ok(Some(state)) = Self::parse(res.body["state"].take()) {
+                        self.state().merge(&state);
+                        self.observed(&peer, &state);
+                        self.acks.lock().insert(peer, Self::digest(&state));
+                    }
+                }
+            });
+        }
+    }
+
+    /// State sent by a peer, None if it sent none
+    fn parse(value: Value) -> Result<Option<S>, serde_json::Error> {
+        match value {
+            Value::Null => Ok(None),
+            value => serde_json::from_value(value).map(Some),
+        }
+    }
+
+    pub fn on_gossip(&self, node: &Node, msg: &Msg) {
+        let Ok(state) = Self::parse(msg.body["state"].clone()) else {
+            return node.reply(msg, Err::MalformedRequest.msg());
+        };
+        if let Some(state) = state {
+            self.state().merge(&state);
+            self.observed(&msg.src, &state);
+        }
+        if let Some(ack) = msg.body["ack"].as_u64() {
+            let acked = self.unacked.lock().remove(&msg.src);
+            match acked {
+                Some((digest, delta)) if digest == ack => self.observed(&msg.src, &delta),
+                // Acknowledging an older reply, the last one was lost
+                _ => {}
+            }
+        }
+        let mut reply = None;
+        if let Some(digest) = msg.body["digest"].as_u64() {
+            if digest != Self::digest(&self.state()) {
+                let delta = self.delta_for(&msg.src);
+                if delta != S::default() {
+                    let unacked = (Self::digest(&delta), delta.clone());
+                    self.unacked.lock().insert(msg.src.clone(), unacked);
+                    reply = Some(delta);
+                }
+            }
+        }
+        node.reply(msg, json!({"type": "gossip_ok", "state": reply}));
+    }
+
+    /// Compare digests with a peer and exchange full states if they differ
+    pub fn sync(&self, node: &Node, peer: String) {
+        let snapshot = self.state().clone();
+        let body = json!({"type": "gossip_sync", "digest": Self::digest(&snapshot)});
+        if let Ok(mut res) = node.rpc(peer.clone(), body) {
+            match Self::parse(res.body["state"].take()) {
+                Err(_) => {}
+                Ok(None) => {
+                    self.seen.lock().insert(peer, snapshot);
+                }
+                Ok(Some(remote)) => {
+                    let delta = {
+                        let mut state = self.state();
+                        state.merge(&remote);
+                        state.delta(&remote)
+                    };
+                    self.seen.lock().insert(peer.clone(), remote);
+                    if delta != S::default() {
+                        let body = json!({"type": "gossip", "state": delta});
+                        if node.rpc(peer.clone(), body).is_ok() {
+                            self.observed(&peer, &delta);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn on_sync(&self, node: &Node, msg: &Msg) {
+        let state = self.state().clone();
+        let in_sync = msg.body["digest"].as_u64() == Some(Self::digest(&state));
+        let reply = if in_sync {
+            self.seen.lock().insert(msg.src.clone(), state);
+            None
+        } else {
+            Some(state)
+        };
+        node.reply(msg, json!({"type": "gossip_sync_ok", "state": reply}));
+    }
+}
@@ -0,0 +1,141 @@
+use std::{collections::BTreeMap, time::Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Kind of history event, as in Jepsen
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Type {
+    /// A client started an operation
+    Invoke,
+    /// The operation took place
+    Ok,
+    /// The operation certainly did not take place
+    Fail,
+    /// The operation may or may not have taken place
+    Info,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Op {
+    pub index: usize,
+    /// Nanoseconds since the start of the test
+    pub time: u64,
+    pub process: u64,
+    #[serde(rename = "type")]
+    pub ty: Type,
+    pub f: String,
+    pub value: Value,
+    /// Read of the final phase once the cluster recovered, as Jepsen `:final?`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub r#final: bool,
+}
+
+/// Invocation with its completion, None if the process never heard back
+#[derive(Debug, Clone, Copy)]
+pub struct Pair<'a> {
+    pub invoke: &'a Op,
+    pub complete: Option<&'a Op>,
+}
+
+impl Pair<'_> {
+    /// Completion type, a missing completion being indeterminate
+    pub fn ty(&self) -> Type {
+        self.complete.map(|op| op.ty).unwrap_or(Type::Info)
+    }
+
+    /// Completion value if known, invocation value otherwise
+    pub fn value(&self) -> &Value {
+        match self.complete {
+            Some(op) if op.ty == Type::Ok => &op.value,
+            _ => &self.invoke.value,
+        }
+    }
+}
+
+/// Sequence of client operations, in real-time order
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct History {
+    pub ops: Vec<Op>,
+    #[serde(skip, default = "Instant::now")]
+    start: Instant,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self {
+            ops: Vec::new(),
+            start: Instant::now(),
+        }
+    }
+
+    /// Empty history timestamping events from `start`, e.g. to line them up
+    /// with a `net::Net` trace
+    pub fn since(start: Instant) -> Self {
+        Self {
+            ops: Vec::new(),
+            start,
+        }
+    }
+
+    pub fn from_ops(ops: Vec<Op>) -> Self {
+        Self {
+            ops,
+            start: Instant::now(),
+        }
+    }
+
+    /// Append an event timestamped now
+    pub fn push(&mut self, process: u64, ty: Type, f: &str, value: Value) -> &Op {
+        let op = Op {
+            index: self.ops.len(),
+            time: self.start.elapsed().as_nanos() as u64,
+            process,
+            ty,
+            f: f.to_owned(),
+            value,
+            r#final: false,
+        };
+        self.ops.push(op);
+        self.ops.last().unwrap()
+    }
+
+    /// Append an event of the final phase, see `Op::final`
+    pub fn push_final(&mut self, process: u64, ty: Type, f: &str, value: Value) -> &Op {
+        self.push(process, ty, f, value);
+        let op = self.ops.last_mut().unwrap();
+        op.r#final = true;
+        op
+    }
+
+    /// Whether the final phase is marked, checkers otherwise taking the
+    /// reads following every update as final
+    pub fn has_final(&self) -> bool {
+        self.ops.iter().any(|op| op.r#final)
+    }
+
+    /// Match every invocation with the next event of the same process
+    pub fn pairs(&self) -> Vec<Pair<'_>> {
+        let mut pairs: Vec<Pair> = Vec::new();
+        let mut pending = BTreeMap::new();
+        for op in &self.ops {
+            if op.ty == Type::Invoke {
+                pending.insert(op.process, pairs.len());
+                pairs.push(Pair {
+                    invoke: op,
+                    complete: None,
+                });
+            } else if let Some(i) = pending.remove(&op.process) {
+                pairs[i].complete = Some(op);
+            }
+        }
+        pairs
+    }
+}
@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+
+/// Cells each key is added to, one per sub-table
+const HASHES: usize = 3;
+
+/// Invertible Bloom lookup table of a set of integers
+///
+/// Subtracting the table of another set with as many cells leaves only the
+/// keys in either set but not both, which most likely decode given two to
+/// three cells per differing key. Reconciling two sets thus costs space in
+/// the size of their difference, not of the sets.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Iblt {
+    cells: Vec<Cell>,
+}
+
+/// Signed key count, xor of the keys and xor of their checksums
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Cell(i64, u64, u64);
+
+impl Cell {
+    fn add(&mut self, key: u64, sign: i64) {
+        self.0 += sign;
+        self.1 ^= key;
+        self.2 ^= hash(key, HASHES);
+    }
+
+    /// Holding a single key, either added or subtracted
+    fn pure(&self) -> bool {
+        self.0.abs() == 1 && self.2 == hash(self.1, HASHES)
+    }
+}
+
+impl Iblt {
+    /// Empty table of at least `cells` cells
+    pub fn new(cells: usize) -> Self {
+        Self {
+            cells: vec![Cell::default(); cells.max(1).div_ceil(HASHES) * HASHES],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn insert(&mut self, key: u64) {
+        self.add(key, 1);
+    }
+
+    /// Remove the keys of `other`, which must have as many cells
+    pub fn subtract(&mut self, other: &Self) {
+        assert_eq!(self.len(), other.len(), "tables of different sizes");
+        for (a, b) in self.cells.iter_mut().zip(&other.cells) {
+            a.0 -= b.0;
+            a.1 ^= b.1;
+            a.2 ^= b.2;
+        }
+    }
+
+    /// Keys inserted but not subtracted, and subtracted but not inserted,
+    /// or None if the table is too full to tell
+    pub fn decode(mut self) -> Option<(Vec<u64>, Vec<u64>)> {
+        let (mut added, mut removed) = (Vec::new(), Vec::new());
+        let mut pure: Vec<usize> = (0..self.len()).filter(|i| self.cells[*i].pure()).collect();
+        while let Some(i) = pure.pop() {
+            let cell = self.cells[i];
+            // Peeling another key may have emptied it meanwhile
+            if !cell.pure() {
+                continue;
+            }
+            let Cell(sign, key, _) = cell;
+            if sign > 0 { &mut added } else { &mut removed }.push(key);
+            for j in self.indices(key) {
+                self.cells[j].add(key, -sign);
+                if self.cells[j].pure() {
+                    pure.push(j);
+                }
+            }
+        }
+        let empty = self.cells.iter().all(|c| *c == Cell::default());
+        empty.then_some((added, removed))
+    }
+
+    fn add(&mut self, key: u64, sign: i64) {
+        for i in self.indices(key) {
+            self.cells[i].add(key, sign);
+        }
+    }
+
+    /// One cell in each sub-table, so that the cells of a key are distinct
+    fn indices(&self, key: u64) -> impl Iterator<Item = usize> {
+        let sub = self.len() / HASHES;
+        (0..HASHES).map(move |i| i * sub + (hash(key, i) % sub as u64) as usize)
+    }
+}
+
+/// SplitMix64 finalizer over the key and the hash function index
+fn hash(key: u64, i: usize) -> u64 {
+    let mix = |mut z: u64| {
+        z = z.wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    };
+    mix(key ^ mix(i as u64))
+}
@@ -0,0 +1,130 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Milliseconds since the Unix epoch, as told by the wall clock
+pub fn now_ms() -> u64 {
+    let since = SystemTime::now().duration_since(UNIX_EPOCH);
+    since.map_or(0, |d| d.as_millis() as u64)
+}
+
+/// Source of `(millisecond, sequence)` pairs which only ever increase, even
+/// when the wall clock goes back
+///
+/// Within a millisecond the sequence counts up to `max_seq`, past which the
+/// clock borrows the next millisecond rather than wait for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clock {
+    last: u64,
+    seq: u64,
+    max_seq: u64,
+}
+
+impl Clock {
+    pub fn new(max_seq: u64) -> Self {
+        Self {
+            last: 0,
+            seq: max_seq,
+            max_seq,
+        }
+    }
+
+    /// Never issue a pair at or before `ms` anymore, e.g. the last
+    /// millisecond reserved before a restart
+    pub fn resume(&mut self, ms: u64) {
+        if ms >= self.last {
+            self.last = ms;
+            self.seq = self.max_seq;
+        }
+    }
+
+    /// Millisecond of the last pair issued
+    pub fn last(&self) -> u64 {
+        self.last
+    }
+
+    /// Next pair at wall clock time `now`, the sequence of a new millisecond
+    /// starting at `start`
+    pub fn tick(&mut self, now: u64, start: u64) -> (u64, u64) {
+        if now > self.last {
+            self.last = now;
+            self.seq = start.min(self.max_seq);
+        } else if self.seq < self.max_seq {
+            self.seq += 1;
+        } else {
+            self.last += 1;
+            self.seq = 0;
+        }
+        (self.last, self.seq)
+    }
+}
+
+/// Start of the Snowflake timestamps, 2020-01-01
+pub const SNOWFLAKE_EPOCH_MS: u64 = 1_577_836_800_000;
+const NODE_BITS: u32 = 10;
+const SEQ_BITS: u32 = 12;
+
+/// Twitter Snowflake ids: 41 bits of milliseconds since `SNOWFLAKE_EPOCH_MS`,
+/// 10 bits of node index and 12 bits of sequence, in 63 bits
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snowflake {
+    node: u64,
+    pub clock: Clock,
+}
+
+impl Snowflake {
+    pub fn new(node: usize) -> Result<Self, String> {
+        if node >> NODE_BITS != 0 {
+            return Err(format!("node index {node} takes over {NODE_BITS} bits"));
+        }
+        Ok(Self {
+            node: node as u64,
+            clock: Clock::new((1 << SEQ_BITS) - 1),
+        })
+    }
+
+    pub fn next(&mut self, now: u64) -> u64 {
+        let (ms, seq) = self.clock.tick(now.max(SNOWFLAKE_EPOCH_MS), 0);
+        (ms - SNOWFLAKE_EPOCH_MS) << (NODE_BITS + SEQ_BITS) | self.node << SEQ_BITS | seq
+    }
+}
+
+/// ULIDs: 48 bits of milliseconds since the Unix epoch and 80 bits of
+/// entropy, written as 26 Crockford base32 digits
+///
+/// The entropy is the node index on 16 bits, which rules out collisions
+/// between nodes, then a 64-bit sequence starting at random each
+/// millisecond as in the monotonic ULID variant.
+#[derive(Debug, Clone)]
+pub struct Ulid {
+    node: u128,
+    pub clock: Clock,
+    rng: fastrand::Rng,
+}
+
+impl Ulid {
+    pub fn new(node: usize) -> Result<Self, String> {
+        if node > u16::MAX as usize {
+            return Err(format!("node index {node} takes over 16 bits"));
+        }
+        Ok(Self {
+            node: node as u128,
+            clock: Clock::new(u64::MAX),
+            rng: fastrand::Rng::new(),
+        })
+    }
+
+    pub fn next(&mut self, now: u64) -> u128 {
+        // Half the range left for the sequence to grow within a millisecond
+        let start = self.rng.u64(..1 << 63);
+        let (ms, seq) = self.clock.tick(now, start);
+        (ms as u128) << 80 | self.node << 64 | seq as u128
+    }
+
+    /// Crockford base32, which sorts as the id does
+    pub fn encode(id: u128) -> String {
+        const DIGITS: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+        (0..26)
+            .rev()
+            .map(|i| DIGITS[(id >> (5 * i)) as usize & 31] as char)
+            .collect()
+    }
+}
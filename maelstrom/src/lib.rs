@@ -1,50 +1,102 @@
+pub mod checker;
+pub mod cluster;
+pub mod crdt;
+pub mod detector;
+pub mod edn;
+pub mod generator;
+pub mod gossip;
+pub mod history;
+pub mod iblt;
+pub mod ids;
+pub mod model;
+pub mod nemesis;
+pub mod net;
+pub mod sharding;
+pub mod shrink;
+pub mod storage;
+pub mod topology;
+
 use std::{
     collections::BTreeMap,
     io::{stdin, stdout, BufRead, Write},
     sync::{
-        atomic::AtomicU64,
+        atomic::{AtomicBool, AtomicU64, Ordering},
         mpsc::{sync_channel, Receiver, SyncSender},
     },
-    thread::spawn,
+    thread::{sleep, spawn},
     time::Duration,
 };
 
+use detector::Detector;
+use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::{json, Value};
+use storage::Storage;
 
 pub struct Node {
     sender: Mutex<SyncSender<Msg>>,
     receiver: Mutex<Receiver<Msg>>,
     id_counter: AtomicU64,
     pending: Mutex<BTreeMap<u64, oneshot::Sender<Result<Msg, Err>>>>,
+    detector: OnceCell<Detector>,
+    closed: AtomicBool,
     pub id: String,
     pub node_ids: Vec<String>,
+    /// State kept across crash-restarts, see `storage::STATE_DIR`
+    pub storage: Storage,
+    /// Restarts of this node so far, counted in `storage` and thus always 0
+    /// unless it is durable
+    pub boot: u64,
 }
 
 impl Node {
+    /// Node on stdin and stdout, blocking until its `init` arrives
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
-        let receiver = Self::receiver();
-        let sender = Self::sender();
+        Self::from_channels(Self::receiver(), Self::sender())
+    }
 
-        let init = receiver.recv().unwrap();
-        assert_eq!(init.body["type"].as_str(), Some("init"));
+    /// Node exchanging messages over channels instead of stdin and stdout,
+    /// e.g. attached to an in-process `net::Net`
+    pub fn from_channels(receiver: Receiver<Msg>, sender: SyncSender<Msg>) -> Self {
+        // Peers may reach a node before its init does, e.g. right after a
+        // restart, and are ignored as it cannot process them yet
+        let init = loop {
+            let msg = receiver.recv().unwrap();
+            if msg.body["type"] == "init" {
+                break msg;
+            }
+        };
 
+        let id = init.body["node_id"]
+            .as_str()
+            .map(|it| it.to_string())
+            .unwrap();
+        let storage = Storage::open(&id).unwrap();
+        // A restarted node must not reuse message ids, or it would take late
+        // replies meant for its previous incarnation as its own
+        let boot = storage
+            .load("boot")
+            .unwrap()
+            .map_or(0, |boot: u64| boot + 1);
+        storage.save("boot", &boot).unwrap();
         let tmp = Self {
-            id: init.body["node_id"]
-                .as_str()
-                .map(|it| it.to_string())
-                .unwrap(),
+            storage,
+            boot,
+            id,
             node_ids: init.body["node_ids"]
                 .as_array()
                 .unwrap()
                 .iter()
                 .map(|it| it.as_str().map(|it| it.to_string()).unwrap())
                 .collect(),
-            id_counter: AtomicU64::new(0),
+            id_counter: AtomicU64::new(boot << 32),
             receiver: Mutex::new(receiver),
             sender: Mutex::new(sender),
             pending: Mutex::new(BTreeMap::new()),
+            detector: OnceCell::new(),
+            closed: AtomicBool::new(false),
         };
         tmp.reply(
             &init,
@@ -62,7 +114,10 @@ impl Node {
             let mut buf = String::with_capacity(1024);
             loop {
                 buf.clear();
-                stdin.read_line(&mut buf).unwrap();
+                // Closing stdin stops the node
+                if stdin.read_line(&mut buf).unwrap() == 0 {
+                    break;
+                }
                 let msg: Msg = serde_json::from_str(&buf).unwrap();
                 eprintln!("{} < {} : {}", msg.dest, msg.src, msg.body);
                 sender.send(msg).unwrap();
@@ -97,12 +152,39 @@ impl Node {
         self.node_ids.iter().filter(|it| **it != self.id)
     }
 
+    /// Opt into failure detection of the other nodes, must be called before `run`
+    pub fn enable_detector(&self, config: detector::Config) -> &Detector {
+        self.detector
+            .get_or_init(|| Detector::new(config, self.other_ids()))
+    }
+
+    pub fn detector(&self) -> Option<&Detector> {
+        self.detector.get()
+    }
+
+    /// Whether a peer is trusted by the failure detector, always true if disabled
+    pub fn is_alive(&self, peer: &str) -> bool {
+        self.detector
+            .get()
+            .map(|d| d.is_alive(peer))
+            .unwrap_or(true)
+    }
+
+    /// Whether `run` has returned, background loops having to stop then so
+    /// that a killed or disconnected node goes away entirely
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
     pub fn reply(&self, to: &Msg, mut body: Value) {
         body["in_reply_to"] = to.body["msg_id"].as_u64().unwrap().into();
         self.send(to.src.clone(), body);
     }
 
     fn send(&self, dest: String, body: Value) {
+        if let Some(detector) = self.detector.get() {
+            detector.sent(&dest);
+        }
         let msg = Msg {
             src: self.id.clone(),
             dest,
@@ -135,6 +217,13 @@ impl Node {
         }
     }
 
+    /// Relay a request to another node and its reply back to the requester
+    pub fn forward(&self, dest: String, msg: &Msg) -> Result<(), Err> {
+        let res = self.rpc(dest, msg.body.clone())?;
+        self.reply(msg, res.body);
+        Ok(())
+    }
+
     pub fn read<M: DeserializeOwned>(&self, kv: KV, key: &str) -> Result<M, Err> {
         self.rpc(kv.id().to_string(), json!({"type": "read", "key": key}))
             .map(|mut m| serde_json::from_value(m.body["value"].take()).unwrap())
@@ -161,9 +250,23 @@ impl Node {
 
     pub fn run<'a>(&'a self, lambda: impl Fn(Msg) + Send + Sync + 'a) {
         std::thread::scope(|s| {
+            if let Some(detector) = self.detector.get() {
+                s.spawn(move || {
+                    while !self.is_closed() {
+                        sleep(detector.tick_interval());
+                        detector.tick(self);
+                    }
+                });
+            }
             let receiver = self.receiver.lock();
-            loop {
-                let msg = receiver.recv().unwrap();
+            // Stops once the transport is closed
+            while let Ok(msg) = receiver.recv() {
+                if let Some(detector) = self.detector.get() {
+                    detector.heartbeat(&msg.src);
+                }
+                if msg.body["type"] == "heartbeat" {
+                    continue;
+                }
                 if let Some(msg_id) = msg.body["in_reply_to"].as_u64() {
                     let task = self.pending.lock().remove(&msg_id);
                     if let Some(task) = task {
@@ -178,10 +281,18 @@ impl Node {
                     s.spawn(|| lambda(msg));
                 }
             }
+            self.closed.store(true, Ordering::SeqCst);
         })
     }
 }
 
+/// FNV-1a hash, stable across processes and builds
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Msg {
     pub src: String,
@@ -222,6 +333,11 @@ pub enum Err {
 }
 
 impl Err {
+    /// Whether the operation certainly did not take place
+    pub fn is_definite(self) -> bool {
+        !matches!(self, Err::Timeout | Err::Crash)
+    }
+
     pub fn msg(self) -> Value {
         let code = match self {
             Err::Timeout => 0,
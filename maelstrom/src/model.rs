@@ -0,0 +1,276 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet, VecDeque},
+    fmt::{self, Debug, Display},
+    hash::Hash,
+};
+
+/// Deterministic node logic without IO, reacting to messages and timers
+/// through an `Out` buffer
+pub trait Process: Clone + Eq + Hash + Debug {
+    type Msg: Clone + Ord + Hash + Debug;
+    type Timer: Clone + Ord + Hash + Debug;
+
+    fn on_start(&mut self, id: usize, out: &mut Out<Self>);
+    fn on_msg(&mut self, id: usize, src: usize, msg: Self::Msg, out: &mut Out<Self>);
+    fn on_timer(&mut self, id: usize, timer: Self::Timer, out: &mut Out<Self>);
+}
+
+/// Effects of a process step
+#[derive(Debug)]
+pub struct Out<P: Process> {
+    sends: Vec<(usize, P::Msg)>,
+    set: Vec<P::Timer>,
+    cancel: Vec<P::Timer>,
+}
+
+impl<P: Process> Out<P> {
+    fn new() -> Self {
+        Self {
+            sends: Vec::new(),
+            set: Vec::new(),
+            cancel: Vec::new(),
+        }
+    }
+
+    pub fn send(&mut self, dest: usize, msg: P::Msg) {
+        self.sends.push((dest, msg));
+    }
+
+    /// Arm a timer, which may fire at any later point
+    pub fn set_timer(&mut self, timer: P::Timer) {
+        self.set.push(timer);
+    }
+
+    pub fn cancel_timer(&mut self, timer: P::Timer) {
+        self.cancel.push(timer);
+    }
+}
+
+/// Message in flight
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Envelope<M> {
+    pub src: usize,
+    pub dest: usize,
+    pub msg: M,
+}
+
+/// Global state: the processes, the messages in flight and the armed timers
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct State<P: Process> {
+    pub procs: Vec<P>,
+    /// Multiset of messages in flight, the network not preserving order
+    pub network: BTreeMap<Envelope<P::Msg>, usize>,
+    pub timers: Vec<BTreeSet<P::Timer>>,
+}
+
+impl<P: Process> State<P> {
+    fn apply(&mut self, id: usize, out: Out<P>) {
+        for (dest, msg) in out.sends {
+            let env = Envelope { src: id, dest, msg };
+            *self.network.entry(env).or_default() += 1;
+        }
+        for timer in out.cancel {
+            self.timers[id].remove(&timer);
+        }
+        self.timers[id].extend(out.set);
+    }
+
+    fn take(&mut self, env: &Envelope<P::Msg>) {
+        match self.network.get_mut(env) {
+            Some(count) if *count > 1 => *count -= 1,
+            _ => {
+                self.network.remove(env);
+            }
+        }
+    }
+
+    fn step(&self, action: &Action<P>) -> Self {
+        let mut next = self.clone();
+        let mut out = Out::new();
+        match action {
+            Action::Deliver(env) => {
+                next.take(env);
+                next.procs[env.dest].on_msg(env.dest, env.src, env.msg.clone(), &mut out);
+                next.apply(env.dest, out);
+            }
+            Action::Drop(env) => next.take(env),
+            Action::Fire(id, timer) => {
+                next.timers[*id].remove(timer);
+                next.procs[*id].on_timer(*id, timer.clone(), &mut out);
+                next.apply(*id, out);
+            }
+        }
+        next
+    }
+
+    fn actions(&self, lossy: bool) -> Vec<Action<P>> {
+        let mut actions = Vec::new();
+        for env in self.network.keys() {
+            actions.push(Action::Deliver(env.clone()));
+            if lossy {
+                actions.push(Action::Drop(env.clone()));
+            }
+        }
+        for (id, timers) in self.timers.iter().enumerate() {
+            actions.extend(timers.iter().map(|t| Action::Fire(id, t.clone())));
+        }
+        actions
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action<P: Process> {
+    Deliver(Envelope<P::Msg>),
+    Drop(Envelope<P::Msg>),
+    Fire(usize, P::Timer),
+}
+
+impl<P: Process> Display for Action<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Deliver(e) => write!(f, "deliver {} -> {} {:?}", e.src, e.dest, e.msg),
+            Action::Drop(e) => write!(f, "drop {} -> {} {:?}", e.src, e.dest, e.msg),
+            Action::Fire(id, timer) => write!(f, "fire {id} {timer:?}"),
+        }
+    }
+}
+
+/// Named property that must hold in every reachable state
+pub struct Invariant<P: Process> {
+    pub name: &'static str,
+    pub holds: fn(&State<P>) -> bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Longest sequence of actions explored
+    pub depth: usize,
+    /// Whether messages may be dropped
+    pub lossy: bool,
+    /// Stop after visiting this many distinct states
+    pub max_states: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            depth: 10,
+            lossy: false,
+            max_states: 1_000_000,
+        }
+    }
+}
+
+/// Shortest sequence of actions from the initial state to a violation
+#[derive(Debug, Clone)]
+pub struct Counterexample<P: Process> {
+    pub invariant: &'static str,
+    pub steps: Vec<(Action<P>, State<P>)>,
+}
+
+impl<P: Process> Display for Counterexample<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "invariant {:?} violated after:", self.invariant)?;
+        for (i, (action, state)) in self.steps.iter().enumerate() {
+            writeln!(f, "{:>3}. {action}", i + 1)?;
+            for (id, proc) in state.procs.iter().enumerate() {
+                writeln!(f, "       {id}: {proc:?}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stats {
+    pub states: usize,
+    /// Depth of the deepest state explored
+    pub depth: usize,
+    /// Whether every state up to `depth` was explored
+    pub complete: bool,
+}
+
+/// Explored state, with its parent and the action leading to it
+struct Visited<P: Process> {
+    state: State<P>,
+    parent: Option<(usize, Action<P>)>,
+    depth: usize,
+}
+
+/// Breadth-first search of the states reachable within `config.depth`
+/// actions, deduplicating visited states
+pub fn check<P: Process>(
+    procs: Vec<P>,
+    config: &Config,
+    invariants: &[Invariant<P>],
+) -> Result<Stats, Counterexample<P>> {
+    let mut init = State {
+        timers: vec![BTreeSet::new(); procs.len()],
+        procs,
+        network: BTreeMap::new(),
+    };
+    for id in 0..init.procs.len() {
+        let mut out = Out::new();
+        init.procs[id].on_start(id, &mut out);
+        init.apply(id, out);
+    }
+
+    let mut seen: HashSet<State<P>> = HashSet::new();
+    let mut states: Vec<Visited<P>> = Vec::new();
+    let mut queue = VecDeque::from([0]);
+    seen.insert(init.clone());
+    states.push(Visited {
+        state: init,
+        parent: None,
+        depth: 0,
+    });
+    let mut stats = Stats {
+        states: 1,
+        depth: 0,
+        complete: true,
+    };
+    while let Some(i) = queue.pop_front() {
+        let Visited { state, depth, .. } = &states[i];
+        stats.depth = stats.depth.max(*depth);
+        if let Some(inv) = invariants.iter().find(|inv| !(inv.holds)(state)) {
+            return Err(trace(&states, i, inv.name));
+        }
+        if *depth == config.depth {
+            continue;
+        }
+        let depth = *depth + 1;
+        for action in state.actions(config.lossy) {
+            let next = states[i].state.step(&action);
+            if seen.contains(&next) {
+                continue;
+            }
+            if states.len() == config.max_states {
+                stats.complete = false;
+                return Ok(stats);
+            }
+            seen.insert(next.clone());
+            queue.push_back(states.len());
+            states.push(Visited {
+                state: next,
+                parent: Some((i, action)),
+                depth,
+            });
+            stats.states += 1;
+        }
+    }
+    Ok(stats)
+}
+
+fn trace<P: Process>(
+    states: &[Visited<P>],
+    mut i: usize,
+    invariant: &'static str,
+) -> Counterexample<P> {
+    let mut steps = Vec::new();
+    while let Some((parent, action)) = &states[i].parent {
+        steps.push((action.clone(), states[i].state.clone()));
+        i = *parent;
+    }
+    steps.reverse();
+    Counterexample { invariant, steps }
+}
@@ -0,0 +1,244 @@
+use std::{
+    collections::BTreeSet,
+    str::FromStr,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::net::Net;
+
+/// Fault family, as in maelstrom `--nemesis partition,kill`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum Kind {
+    /// Two random groups
+    Partition,
+    /// A majority and a minority
+    Majority,
+    /// Two halves that only talk through a bridge node
+    Bridge,
+    Drop,
+    Duplicate,
+    Reorder,
+    /// Latency spikes on the links of a node
+    Latency,
+    Pause,
+    Kill,
+}
+
+impl FromStr for Kind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_value(s.into()).map_err(|_| format!("unknown nemesis {s}"))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "f", rename_all = "kebab-case")]
+pub enum Fault {
+    /// Cut the links between nodes of different groups, the bridge if any
+    /// keeping its links to everyone
+    Partition {
+        groups: Vec<Vec<String>>,
+        bridge: Option<String>,
+    },
+    Heal,
+    Drop {
+        p: f64,
+    },
+    Duplicate {
+        p: f64,
+    },
+    Reorder {
+        p: f64,
+    },
+    /// Extra latency on directed links, zero removing it
+    Latency {
+        links: Vec<(String, String)>,
+        ms: u64,
+    },
+    Pause {
+        node: String,
+    },
+    Resume {
+        node: String,
+    },
+    Kill {
+        node: String,
+    },
+    Restart {
+        node: String,
+    },
+}
+
+/// Control over the node processes, for kill faults
+pub trait Lifecycle {
+    fn kill(&self, node: &str);
+    /// Start the node again with its `init`
+    fn restart(&self, node: &str);
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Step {
+    /// Milliseconds since the start of the run
+    pub at: u64,
+    #[serde(flatten)]
+    pub fault: Fault,
+}
+
+/// Fault schedule, replayed identically from its serialized form
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Plan {
+    pub seed: u64,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub kinds: BTreeSet<Kind>,
+    /// Time between the start and the end of faults, as maelstrom
+    /// `--nemesis-interval`
+    pub interval: Duration,
+    pub time_limit: Duration,
+    /// Probability of the drop, duplicate and reorder faults
+    pub p: f64,
+    pub seed: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            kinds: BTreeSet::new(),
+            interval: Duration::from_secs(10),
+            time_limit: Duration::from_secs(60),
+            p: 0.2,
+            seed: 0,
+        }
+    }
+}
+
+impl Plan {
+    /// Alternate between a random fault of the configured kinds and a quiet
+    /// period, every fault being over by the time limit
+    pub fn generate(config: &Config, nodes: &[String]) -> Self {
+        let rng = fastrand::Rng::with_seed(config.seed);
+        let kinds: Vec<Kind> = config.kinds.iter().copied().collect();
+        let interval = config.interval.as_millis().max(1) as u64;
+        let end = config.time_limit.as_millis() as u64;
+        let mut steps = Vec::new();
+        let mut at = interval;
+        while !kinds.is_empty() && !nodes.is_empty() && at < end {
+            let (start, stop) = faults(kinds[rng.usize(..kinds.len())], config.p, nodes, &rng);
+            steps.push(Step { at, fault: start });
+            steps.push(Step {
+                at: (at + interval).min(end),
+                fault: stop,
+            });
+            at += 2 * interval;
+        }
+        Self {
+            seed: config.seed,
+            steps,
+        }
+    }
+
+    /// Apply every step on time, blocking until the last one
+    pub fn run(&self, net: &Net, nodes: &[String], lifecycle: &dyn Lifecycle) {
+        net.faults().nodes = nodes.iter().cloned().collect();
+        let start = Instant::now();
+        for step in &self.steps {
+            let at = start + Duration::from_millis(step.at);
+            sleep(at.saturating_duration_since(Instant::now()));
+            apply(&step.fault, net, lifecycle);
+        }
+    }
+}
+
+/// A fault of the given kind and the fault ending it
+fn faults(kind: Kind, p: f64, nodes: &[String], rng: &fastrand::Rng) -> (Fault, Fault) {
+    let mut shuffled = nodes.to_vec();
+    rng.shuffle(&mut shuffled);
+    let n = shuffled.len();
+    let partition = |nodes: &[String], split: usize, bridge: Option<&String>| Fault::Partition {
+        groups: vec![nodes[..split].to_vec(), nodes[split..].to_vec()],
+        bridge: bridge.cloned(),
+    };
+    let node = shuffled[0].clone();
+    match kind {
+        Kind::Partition => {
+            let split = rng.usize(1..n.max(2)).min(n);
+            (partition(&shuffled, split, None), Fault::Heal)
+        }
+        Kind::Majority => (partition(&shuffled, n / 2, None), Fault::Heal),
+        Kind::Bridge => {
+            let (bridge, rest) = shuffled.split_last().unwrap();
+            (partition(rest, rest.len() / 2, Some(bridge)), Fault::Heal)
+        }
+        Kind::Drop => (Fault::Drop { p }, Fault::Drop { p: 0. }),
+        Kind::Duplicate => (Fault::Duplicate { p }, Fault::Duplicate { p: 0. }),
+        Kind::Reorder => (Fault::Reorder { p }, Fault::Reorder { p: 0. }),
+        Kind::Latency => {
+            let links: Vec<(String, String)> = nodes
+                .iter()
+                .filter(|other| **other != node)
+                .flat_map(|other| [(node.clone(), other.clone()), (other.clone(), node.clone())])
+                .collect();
+            let ms = rng.u64(100..1000);
+            let stop = Fault::Latency {
+                links: links.clone(),
+                ms: 0,
+            };
+            (Fault::Latency { links, ms }, stop)
+        }
+        Kind::Pause => (Fault::Pause { node: node.clone() }, Fault::Resume { node }),
+        Kind::Kill => (Fault::Kill { node: node.clone() }, Fault::Restart { node }),
+    }
+}
+
+/// Apply a fault right away, link faults only affecting messages sent after
+pub fn apply(fault: &Fault, net: &Net, lifecycle: &dyn Lifecycle) {
+    match fault {
+        Fault::Kill { node } => return lifecycle.kill(node),
+        Fault::Restart { node } => return lifecycle.restart(node),
+        _ => {}
+    }
+    let mut faults = net.faults();
+    match fault {
+        Fault::Partition { groups, .. } => {
+            for (i, a) in groups.iter().enumerate() {
+                for b in groups.iter().skip(i + 1) {
+                    for (x, y) in a.iter().flat_map(|x| b.iter().map(move |y| (x, y))) {
+                        faults.cut.insert((x.clone(), y.clone()));
+                        faults.cut.insert((y.clone(), x.clone()));
+                    }
+                }
+            }
+        }
+        Fault::Heal => faults.cut.clear(),
+        Fault::Drop { p } => faults.drop = *p,
+        Fault::Duplicate { p } => faults.duplicate = *p,
+        Fault::Reorder { p } => faults.reorder = *p,
+        Fault::Latency { links, ms: 0 } => {
+            for link in links {
+                faults.spikes.remove(link);
+            }
+        }
+        Fault::Latency { links, ms } => {
+            for link in links {
+                faults
+                    .spikes
+                    .insert(link.clone(), Duration::from_millis(*ms));
+            }
+        }
+        Fault::Pause { node } => {
+            faults.paused.insert(node.clone());
+        }
+        Fault::Resume { node } => {
+            faults.paused.remove(node);
+        }
+        Fault::Kill { .. } | Fault::Restart { .. } => unreachable!(),
+    }
+}
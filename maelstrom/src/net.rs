@@ -0,0 +1,320 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    str::FromStr,
+    sync::{
+        mpsc::{channel, sync_channel, Receiver, RecvTimeoutError, Sender, SyncSender},
+        Arc,
+    },
+    thread::spawn,
+    time::{Duration, Instant},
+};
+
+use parking_lot::{Mutex, MutexGuard, RwLock};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{Err, Msg, KV};
+
+/// Distribution of message latencies, as maelstrom `--latency-dist`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dist {
+    Constant,
+    /// Uniform between zero and twice the latency
+    Uniform,
+    Exponential,
+}
+
+impl FromStr for Dist {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "constant" => Dist::Constant,
+            "uniform" => Dist::Uniform,
+            "exponential" => Dist::Exponential,
+            _ => return Err(format!("unknown latency distribution {s}")),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Mean one-way latency
+    pub latency: Duration,
+    pub dist: Dist,
+    pub seed: u64,
+    /// Record every message in a trace
+    pub trace: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            latency: Duration::ZERO,
+            dist: Dist::Constant,
+            seed: 0,
+            trace: false,
+        }
+    }
+}
+
+/// Delay before retrying the delivery of a message held by a pause
+const PAUSE_POLL: Duration = Duration::from_millis(10);
+
+/// Extra delay of reordered messages, at most
+const REORDER_WINDOW: Duration = Duration::from_millis(100);
+
+/// Faults currently applied to the links between `nodes`, clients and
+/// services being always reachable
+#[derive(Debug, Clone, Default)]
+pub struct Faults {
+    pub nodes: BTreeSet<String>,
+    /// Directed links dropping every message
+    pub cut: BTreeSet<(String, String)>,
+    /// Probabilities of dropping, duplicating and delaying a message past
+    /// the following ones
+    pub drop: f64,
+    pub duplicate: f64,
+    pub reorder: f64,
+    /// Extra latency of directed links
+    pub spikes: BTreeMap<(String, String), Duration>,
+    /// Nodes whose messages are held until they resume
+    pub paused: BTreeSet<String>,
+}
+
+impl Faults {
+    fn link(&self, msg: &Msg) -> bool {
+        self.nodes.contains(&msg.src) && self.nodes.contains(&msg.dest)
+    }
+}
+
+/// Message of a trace
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Event {
+    /// Nanoseconds since the network started, when the message was
+    /// delivered or dropped
+    pub time: u64,
+    pub msg: Msg,
+    pub dropped: bool,
+}
+
+struct Shared {
+    start: Instant,
+    routes: RwLock<BTreeMap<String, Sender<Msg>>>,
+    faults: Mutex<Faults>,
+    trace: Option<Mutex<Vec<Event>>>,
+}
+
+impl Shared {
+    fn record(&self, msg: &Msg, dropped: bool) {
+        if let Some(trace) = &self.trace {
+            trace.lock().push(Event {
+                time: self.start.elapsed().as_nanos() as u64,
+                msg: msg.clone(),
+                dropped,
+            });
+        }
+    }
+}
+
+/// In-process network routing messages between nodes, clients and services
+///
+/// Every participant joins with an id and gets a pair of channels, which
+/// fits `Node::from_channels`. Dropping the network disconnects everyone.
+pub struct Net {
+    shared: Arc<Shared>,
+    inbox: SyncSender<Msg>,
+}
+
+impl Net {
+    pub fn new(config: Config) -> Self {
+        let shared = Arc::new(Shared {
+            start: Instant::now(),
+            routes: Default::default(),
+            faults: Default::default(),
+            trace: config.trace.then(Default::default),
+        });
+        let (inbox, receiver) = sync_channel(1024);
+        let tmp = shared.clone();
+        spawn(move || route(&tmp, receiver, &config));
+        Self { shared, inbox }
+    }
+
+    /// Attach a participant, replacing any previous one with the same id
+    pub fn join(&self, id: &str) -> (Receiver<Msg>, SyncSender<Msg>) {
+        let (sender, receiver) = channel();
+        self.shared.routes.write().insert(id.to_owned(), sender);
+        (receiver, self.inbox.clone())
+    }
+
+    /// Detach a participant, messages to it being dropped from now on
+    pub fn leave(&self, id: &str) {
+        self.shared.routes.write().remove(id);
+    }
+
+    pub fn start(&self) -> Instant {
+        self.shared.start
+    }
+
+    /// Messages delivered or dropped so far, empty unless `Config::trace`
+    pub fn trace(&self) -> Vec<Event> {
+        self.shared
+            .trace
+            .as_ref()
+            .map(|t| t.lock().clone())
+            .unwrap_or_default()
+    }
+
+    /// Faults applied to messages sent from now on
+    pub fn faults(&self) -> MutexGuard<'_, Faults> {
+        self.shared.faults.lock()
+    }
+
+    /// Start a maelstrom KV service
+    ///
+    /// lin-kv and lww-kv are backed by a single linearizable map, which is a
+    /// legal behaviour for lww-kv as well. seq-kv lags as far as sequential
+    /// consistency allows: a client reads the store as of its own last
+    /// write or cas, missing everything the others did since.
+    pub fn serve(&self, kv: KV) {
+        let (receiver, sender) = self.join(kv.id());
+        spawn(move || {
+            let mut store: BTreeMap<String, Value> = BTreeMap::new();
+            // Every write in order, and how many of them each client saw
+            let mut log: Vec<(String, Value)> = Vec::new();
+            let mut seen: BTreeMap<String, usize> = BTreeMap::new();
+            for msg in receiver {
+                let seq = matches!(kv, KV::Seq);
+                let res = if seq && msg.body["type"] == "read" {
+                    let seen = seen.get(&msg.src).map_or(&log[..0], |n| &log[..*n]);
+                    stale_read(seen, &msg.body)
+                } else {
+                    kv_step(&mut store, &msg.body)
+                };
+                if seq && msg.body["type"] != "read" {
+                    if res.is_ok() {
+                        let key = msg.body["key"].to_string();
+                        let value = store[&key].clone();
+                        log.push((key, value));
+                    }
+                    // Even a failed cas observed the latest value
+                    seen.insert(msg.src.clone(), log.len());
+                }
+                let mut body = res.unwrap_or_else(|e| e.msg());
+                body["in_reply_to"] = msg.body["msg_id"].clone();
+                let reply = Msg {
+                    src: kv.id().to_owned(),
+                    dest: msg.src,
+                    body,
+                };
+                if sender.send(reply).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+impl Drop for Net {
+    fn drop(&mut self) {
+        self.shared.routes.write().clear();
+    }
+}
+
+fn kv_step(store: &mut BTreeMap<String, Value>, body: &Value) -> Result<Value, Err> {
+    let key = body["key"].to_string();
+    match body["type"].as_str() {
+        Some("read") => {
+            let value = store.get(&key).ok_or(Err::KeyDoesNotExist)?;
+            Ok(json!({"type": "read_ok", "value": value}))
+        }
+        Some("write") => {
+            store.insert(key, body["value"].clone());
+            Ok(json!({"type": "write_ok"}))
+        }
+        Some("cas") => {
+            match store.get(&key) {
+                Some(curr) if *curr != body["from"] => return Err(Err::PreconditionFailed),
+                None if body["create_if_not_exists"] != true => return Err(Err::KeyDoesNotExist),
+                _ => {}
+            }
+            store.insert(key, body["to"].clone());
+            Ok(json!({"type": "cas_ok"}))
+        }
+        _ => Err(Err::NotSupported),
+    }
+}
+
+/// Read of a key as of the writes a client saw
+fn stale_read(log: &[(String, Value)], body: &Value) -> Result<Value, Err> {
+    let key = body["key"].to_string();
+    let (_, value) = log
+        .iter()
+        .rev()
+        .find(|(k, _)| *k == key)
+        .ok_or(Err::KeyDoesNotExist)?;
+    Ok(json!({"type": "read_ok", "value": value}))
+}
+
+/// Deliver messages once their latency elapsed, until every sender is gone
+fn route(shared: &Shared, inbox: Receiver<Msg>, config: &Config) {
+    let rng = fastrand::Rng::with_seed(config.seed);
+    let mut queue: BTreeMap<(Instant, u64), Msg> = BTreeMap::new();
+    for seq in 0.. {
+        let next = queue.keys().next().map(|(at, _)| *at);
+        let msg = match next {
+            Some(at) => inbox.recv_timeout(at.saturating_duration_since(Instant::now())),
+            None => inbox.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match msg {
+            Ok(msg) => {
+                let faults = shared.faults.lock();
+                let link = (msg.src.clone(), msg.dest.clone());
+                let faulty = faults.link(&msg);
+                let mut latency = config.latency.mul_f64(match config.dist {
+                    Dist::Constant => 1.,
+                    Dist::Uniform => 2. * rng.f64(),
+                    Dist::Exponential => -(1. - rng.f64()).ln(),
+                });
+                if !faulty {
+                    queue.insert((Instant::now() + latency, 2 * seq), msg);
+                } else if !faults.cut.contains(&link) && rng.f64() >= faults.drop {
+                    latency += faults.spikes.get(&link).copied().unwrap_or_default();
+                    if rng.f64() < faults.reorder {
+                        latency += REORDER_WINDOW.mul_f64(rng.f64());
+                    }
+                    if rng.f64() < faults.duplicate {
+                        let again = latency + REORDER_WINDOW.mul_f64(rng.f64());
+                        // Odd sequence numbers cannot collide with the even ones
+                        queue.insert((Instant::now() + again, 2 * seq + 1), msg.clone());
+                    }
+                    queue.insert((Instant::now() + latency, 2 * seq), msg);
+                } else {
+                    shared.record(&msg, true);
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        let now = Instant::now();
+        let mut held = Vec::new();
+        while let Some(entry) = queue.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+            let ((_, seq), msg) = entry.remove_entry();
+            let paused = &shared.faults.lock().paused;
+            if paused.contains(&msg.src) || paused.contains(&msg.dest) {
+                held.push((seq, msg));
+            } else {
+                let routes = shared.routes.read();
+                // The participant may be gone or have stopped listening
+                let sent = routes.get(&msg.dest).map(|r| r.send(msg.clone()).is_ok());
+                shared.record(&msg, sent != Some(true));
+            }
+        }
+        for (seq, msg) in held {
+            queue.insert((now + PAUSE_POLL, seq), msg);
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{fnv1a, Msg, Node};
+
+/// Position of a key or a virtual node on the ring
+pub fn point(key: &str) -> u64 {
+    // FNV alone is poorly mixed on short similar strings, finish with fmix64
+    let mut h = fnv1a(key.as_bytes());
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51afd7ed558ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ceb9fe1a85ec53);
+    h ^ (h >> 33)
+}
+
+/// Consistent-hash ring with virtual nodes
+///
+/// A key belongs to the first virtual node clockwise from its point, its
+/// replicas are the next distinct nodes. Adding or removing a node only
+/// moves the keys of the arcs it gains or loses.
+#[derive(Debug, Clone)]
+pub struct Ring {
+    vnodes: usize,
+    ring: BTreeMap<u64, String>,
+    nodes: BTreeSet<String>,
+}
+
+/// Arc of the ring whose replica set changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Move {
+    /// Exclusive start of the arc, may wrap around zero
+    pub start: u64,
+    /// Inclusive end of the arc
+    pub end: u64,
+    pub from: Vec<String>,
+    pub to: Vec<String>,
+}
+
+impl Move {
+    pub fn contains(&self, key: &str) -> bool {
+        let p = point(key);
+        if self.start < self.end {
+            self.start < p && p <= self.end
+        } else {
+            self.start < p || p <= self.end
+        }
+    }
+}
+
+impl Ring {
+    pub fn new<'a>(nodes: impl IntoIterator<Item = &'a String>, vnodes: usize) -> Self {
+        let mut tmp = Self {
+            vnodes,
+            ring: BTreeMap::new(),
+            nodes: BTreeSet::new(),
+        };
+        for node in nodes {
+            tmp.add(node);
+        }
+        tmp
+    }
+
+    pub fn add(&mut self, node: &str) {
+        if self.nodes.insert(node.to_owned()) {
+            for i in 0..self.vnodes {
+                self.ring
+                    .insert(point(&format!("{node}#{i}")), node.to_owned());
+            }
+        }
+    }
+
+    pub fn remove(&mut self, node: &str) {
+        if self.nodes.remove(node) {
+            self.ring.retain(|_, n| n != node);
+        }
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &String> {
+        self.nodes.iter()
+    }
+
+    /// Virtual nodes clockwise from a point, starting with its successor
+    fn walk(&self, p: u64) -> impl Iterator<Item = &String> {
+        self.ring
+            .range(p..)
+            .chain(self.ring.range(..p))
+            .map(|(_, n)| n)
+    }
+
+    fn replicas_at(&self, p: u64, n: usize) -> Vec<&String> {
+        let mut replicas: Vec<&String> = Vec::with_capacity(n);
+        for node in self.walk(p) {
+            if replicas.len() == n.min(self.nodes.len()) {
+                break;
+            }
+            if !replicas.contains(&node) {
+                replicas.push(node);
+            }
+        }
+        replicas
+    }
+
+    pub fn owner(&self, key: &str) -> Option<&String> {
+        self.walk(point(key)).next()
+    }
+
+    /// The `n` distinct nodes responsible for a key, owner first
+    pub fn replicas(&self, key: &str, n: usize) -> Vec<&String> {
+        self.replicas_at(point(key), n)
+    }
+
+    /// Arcs whose replica set differs between this ring and `new`
+    pub fn rebalance(&self, new: &Ring, replicas: usize) -> Vec<Move> {
+        let bounds: BTreeSet<u64> = self.ring.keys().chain(new.ring.keys()).copied().collect();
+        let mut moves: Vec<Move> = Vec::new();
+        let Some(last) = bounds.last().copied() else {
+            return moves;
+        };
+        let mut start = last;
+        for end in bounds {
+            let from = self.replicas_at(end, replicas);
+            let to = new.replicas_at(end, replicas);
+            if from != to {
+                let from = from.into_iter().cloned().collect();
+                let to = to.into_iter().cloned().collect();
+                match moves.last_mut() {
+                    // Coalesce adjacent arcs moving between the same replicas
+                    Some(prev) if prev.end == start && prev.from == from && prev.to == to => {
+                        prev.end = end
+                    }
+                    _ => moves.push(Move {
+                        start,
+                        end,
+                        from,
+                        to,
+                    }),
+                }
+            }
+            start = end;
+        }
+        moves
+    }
+
+    /// Handle locally if we own the key, otherwise forward the request to the
+    /// owner and relay its reply. Return true if the message was forwarded.
+    pub fn route(&self, node: &Node, key: &str, msg: &Msg) -> bool {
+        match self.owner(key) {
+            Some(owner) if *owner != node.id => {
+                if let Err(e) = node.forward(owner.clone(), msg) {
+                    node.reply(msg, e.msg());
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Share of the ring owned by each node, to check the balance
+    pub fn load(&self) -> BTreeMap<&String, u64> {
+        let mut load = BTreeMap::new();
+        let Some(mut prev) = self.ring.keys().last().copied() else {
+            return load;
+        };
+        for (p, node) in &self.ring {
+            let arc = match p.wrapping_sub(prev) {
+                0 => u64::MAX,
+                arc => arc,
+            };
+            let curr: &mut u64 = load.entry(node).or_default();
+            *curr = curr.saturating_add(arc);
+            prev = *p;
+        }
+        load
+    }
+}
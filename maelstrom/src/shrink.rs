@@ -0,0 +1,137 @@
+use std::{collections::BTreeSet, thread::scope};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    generator::{self, Request},
+    history::History,
+    nemesis::{Lifecycle, Plan},
+    net::{Event, Net},
+};
+
+/// Fault schedule and client requests of a run
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Case {
+    pub plan: Plan,
+    pub requests: Vec<Request>,
+}
+
+/// Minimized case with the messages of its failing window
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Repro {
+    pub case: Case,
+    pub trace: Vec<Event>,
+}
+
+/// Run a case on initialized nodes, the plan alongside the requests
+pub fn replay(
+    net: &Net,
+    nodes: &[String],
+    lifecycle: &(dyn Lifecycle + Sync),
+    config: &generator::Config,
+    case: &Case,
+) -> History {
+    scope(|s| {
+        s.spawn(|| case.plan.run(net, nodes, lifecycle));
+        generator::run_script(net, nodes, config, &case.requests)
+    })
+}
+
+/// Delta debugging: a 1-minimal subset of `items` for which `fails` holds,
+/// assuming it holds for all of them
+///
+/// Removing any single item of the result makes the failure disappear,
+/// although a smaller failing subset may exist.
+pub fn ddmin<T: Clone>(items: &[T], mut fails: impl FnMut(&[T]) -> bool) -> Vec<T> {
+    let mut items = items.to_vec();
+    if fails(&[]) {
+        return Vec::new();
+    }
+    let mut n = 2;
+    'outer: while items.len() >= 2 {
+        let size = items.len().div_ceil(n);
+        let chunks: Vec<Vec<T>> = items.chunks(size).map(|c| c.to_vec()).collect();
+        for chunk in &chunks {
+            if fails(chunk) {
+                items = chunk.clone();
+                n = 2;
+                continue 'outer;
+            }
+        }
+        if chunks.len() > 2 {
+            for i in 0..chunks.len() {
+                let rest: Vec<T> = chunks
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .flat_map(|(_, c)| c.iter().cloned())
+                    .collect();
+                if fails(&rest) {
+                    items = rest;
+                    n = (n - 1).max(2);
+                    continue 'outer;
+                }
+            }
+        }
+        if n >= items.len() {
+            break;
+        }
+        n = (2 * n).min(items.len());
+    }
+    items
+}
+
+/// Minimize the faults, then the requests, of a case for which `fails` holds
+///
+/// Faults are removed along with the step ending them, as generated plans
+/// alternate between the two, so that every remaining fault still ends.
+pub fn shrink(case: &Case, mut fails: impl FnMut(&Case) -> bool) -> Case {
+    let faults: Vec<_> = case.plan.steps.chunks(2).map(|c| c.to_vec()).collect();
+    let faults = ddmin(&faults, |faults| {
+        fails(&Case {
+            plan: Plan {
+                seed: case.plan.seed,
+                steps: faults.concat(),
+            },
+            requests: case.requests.clone(),
+        })
+    });
+    let plan = Plan {
+        seed: case.plan.seed,
+        steps: faults.concat(),
+    };
+    let requests = ddmin(&case.requests, |requests| {
+        fails(&Case {
+            plan: plan.clone(),
+            requests: requests.to_vec(),
+        })
+    });
+    Case { plan, requests }
+}
+
+/// Time span in nanoseconds of the operations a checker blames, given as
+/// history indices of their invocations or completions, from the earliest
+/// invocation to the latest completion or the end of the history if unknown
+pub fn span(history: &History, blamed: impl IntoIterator<Item = usize>) -> Option<(u64, u64)> {
+    let blamed: BTreeSet<usize> = blamed.into_iter().collect();
+    let end = history.ops.last()?.time;
+    history
+        .pairs()
+        .iter()
+        .filter(|p| {
+            blamed.contains(&p.invoke.index)
+                || p.complete.is_some_and(|op| blamed.contains(&op.index))
+        })
+        .map(|p| (p.invoke.time, p.complete.map_or(end, |op| op.time)))
+        .reduce(|(from, to), (start, stop)| (from.min(start), to.max(stop)))
+}
+
+/// Messages of a trace between two times in nanoseconds, such as the `span`
+/// of the operations reported by a checker
+pub fn window(trace: &[Event], from: u64, to: u64) -> Vec<Event> {
+    trace
+        .iter()
+        .filter(|e| from <= e.time && e.time <= to)
+        .cloned()
+        .collect()
+}
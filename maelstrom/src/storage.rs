@@ -0,0 +1,146 @@
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Environment variable naming the directory holding a subdirectory per node
+pub const STATE_DIR: &str = "MAELSTROM_STATE_DIR";
+
+/// Durable state directory of a node, surviving crash-restarts
+///
+/// Without a directory, saves and appends are no-ops and loads find nothing,
+/// as if every start were the first.
+#[derive(Debug, Clone)]
+pub struct Storage {
+    dir: Option<PathBuf>,
+}
+
+impl Storage {
+    /// Directory of the node under `$MAELSTROM_STATE_DIR`, in memory only if
+    /// the variable is unset
+    pub fn open(node_id: &str) -> io::Result<Self> {
+        match env::var_os(STATE_DIR) {
+            Some(root) => Self::at(Path::new(&root).join(node_id)),
+            None => Ok(Self::memory()),
+        }
+    }
+
+    pub fn at(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir: Some(dir) })
+    }
+
+    pub fn memory() -> Self {
+        Self { dir: None }
+    }
+
+    pub fn is_durable(&self) -> bool {
+        self.dir.is_some()
+    }
+
+    /// Value last saved under `name`, if any
+    pub fn load<T: DeserializeOwned>(&self, name: &str) -> io::Result<Option<T>> {
+        let Some(dir) = &self.dir else {
+            return Ok(None);
+        };
+        match fs::read(dir.join(name)) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Replace the value under `name`, either entirely or not at all
+    ///
+    /// The value is written and synced to a temporary file renamed over the
+    /// previous one, the directory then being synced for the rename to last.
+    pub fn save<T: Serialize>(&self, name: &str, value: &T) -> io::Result<()> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+        let tmp = dir.join(format!(".{name}.tmp"));
+        let mut file = File::create(&tmp)?;
+        file.write_all(&serde_json::to_vec(value)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp, dir.join(name))?;
+        File::open(dir)?.sync_all()
+    }
+
+    /// Open the append-only log `name` along with its entries
+    ///
+    /// An entry torn by a crash mid-append is truncated away, every entry
+    /// before it having been synced.
+    pub fn log<T: Serialize + DeserializeOwned>(&self, name: &str) -> io::Result<(Log<T>, Vec<T>)> {
+        let Some(dir) = &self.dir else {
+            return Ok((Log::new(None), Vec::new()));
+        };
+        let path = dir.join(name);
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        let mut entries = Vec::new();
+        let mut valid = 0;
+        for line in bytes.split_inclusive(|b| *b == b'\n') {
+            let Some(json) = line.strip_suffix(b"\n") else {
+                break;
+            };
+            let Ok(entry) = serde_json::from_slice(json) else {
+                break;
+            };
+            entries.push(entry);
+            valid += line.len();
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        if valid < bytes.len() {
+            file.set_len(valid as u64)?;
+            file.sync_all()?;
+        }
+        File::open(dir)?.sync_all()?;
+        Ok((Log::new(Some(file)), entries))
+    }
+}
+
+/// Append-only log of JSON lines, each append synced before returning
+#[derive(Debug)]
+pub struct Log<T> {
+    file: Option<File>,
+    entry: PhantomData<fn(T)>,
+}
+
+impl<T: Serialize> Log<T> {
+    fn new(file: Option<File>) -> Self {
+        Self {
+            file,
+            entry: PhantomData,
+        }
+    }
+
+    pub fn append(&mut self, entry: &T) -> io::Result<()> {
+        self.append_all([entry])
+    }
+
+    /// Append several entries with a single sync
+    pub fn append_all<'a>(&mut self, entries: impl IntoIterator<Item = &'a T>) -> io::Result<()>
+    where
+        T: 'a,
+    {
+        let Some(file) = &mut self.file else {
+            return Ok(());
+        };
+        let mut buf = Vec::new();
+        for entry in entries {
+            serde_json::to_writer(&mut buf, entry)?;
+            buf.push(b'\n');
+        }
+        file.write_all(&buf)?;
+        file.sync_data()
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+/// Undirected dissemination overlay, in the same shape as maelstrom `topology`
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Topology {
+    neighbours: BTreeMap<String, BTreeSet<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    pub nodes: usize,
+    pub edges: usize,
+    /// Longest shortest path, None if the graph is disconnected
+    pub diameter: Option<usize>,
+    pub min_fanout: usize,
+    pub max_fanout: usize,
+    pub mean_fanout: f64,
+}
+
+impl Topology {
+    /// Graph without edges
+    pub fn empty(ids: &[String]) -> Self {
+        Self {
+            neighbours: ids.iter().map(|id| (id.clone(), BTreeSet::new())).collect(),
+        }
+    }
+
+    fn from_edges(ids: &[String], edges: impl IntoIterator<Item = (usize, usize)>) -> Self {
+        let mut tmp = Self::empty(ids);
+        for (a, b) in edges {
+            tmp.link(&ids[a], &ids[b]);
+        }
+        tmp
+    }
+
+    pub fn link(&mut self, a: &str, b: &str) {
+        if a != b {
+            self.neighbours
+                .entry(a.to_owned())
+                .or_default()
+                .insert(b.to_owned());
+            self.neighbours
+                .entry(b.to_owned())
+                .or_default()
+                .insert(a.to_owned());
+        }
+    }
+
+    /// Every node connected to a single hub
+    pub fn star(ids: &[String], hub: &str) -> Self {
+        let mut tmp = Self::empty(ids);
+        for id in ids {
+            tmp.link(hub, id);
+        }
+        tmp
+    }
+
+    /// Balanced tree where each node has up to `k` children, rooted at the first id
+    pub fn tree(ids: &[String], k: usize) -> Self {
+        assert!(k > 0, "a tree needs at least one child per node");
+        Self::from_edges(ids, (1..ids.len()).map(|i| ((i - 1) / k, i)))
+    }
+
+    /// Tree of depth at most two, with as few children per node as allows it
+    pub fn two_level(ids: &[String]) -> Self {
+        let n = ids.len();
+        let k = (1..n.max(2)).find(|k| 1 + k + k * k >= n).unwrap_or(1);
+        Self::tree(ids, k)
+    }
+
+    /// Square grid linking each node to its horizontal and vertical
+    /// neighbours, maelstrom's default topology
+    pub fn grid(ids: &[String]) -> Self {
+        let n = ids.len();
+        let side = (1..=n).find(|s| s * s >= n).unwrap_or(0);
+        let right = (0..n)
+            .filter(|i| (i + 1) % side != 0 && i + 1 < n)
+            .map(|i| (i, i + 1));
+        let down = (0..n.saturating_sub(side)).map(|i| (i, i + side));
+        Self::from_edges(ids, right.chain(down))
+    }
+
+    pub fn ring(ids: &[String]) -> Self {
+        let n = ids.len();
+        Self::from_edges(ids, (0..n).map(|i| (i, (i + 1) % n)))
+    }
+
+    /// Random graph where every node has exactly `k` neighbours
+    ///
+    /// Starts from a regular lattice and randomizes it with degree-preserving
+    /// edge swaps, retrying until the result is connected.
+    pub fn random_regular(ids: &[String], k: usize, seed: u64) -> Self {
+        let n = ids.len();
+        assert!(
+            k < n && (n * k).is_multiple_of(2),
+            "no {k}-regular graph on {n} nodes"
+        );
+        let rng = fastrand::Rng::with_seed(seed);
+        let mut lattice = BTreeSet::new();
+        for i in 0..n {
+            for j in 1..=k / 2 {
+                lattice.insert(edge(i, (i + j) % n));
+            }
+            if !k.is_multiple_of(2) {
+                lattice.insert(edge(i, (i + n / 2) % n));
+            }
+        }
+        loop {
+            let mut edges: Vec<(usize, usize)> = lattice.iter().copied().collect();
+            let mut set = lattice.clone();
+            for _ in 0..edges.len() * 10 {
+                let (x, y) = (rng.usize(..edges.len()), rng.usize(..edges.len()));
+                let ((a, b), (c, d)) = (edges[x], edges[y]);
+                let (ab, cd) = if rng.bool() {
+                    ((a, d), (c, b))
+                } else {
+                    ((a, c), (b, d))
+                };
+                let (ab, cd) = (edge(ab.0, ab.1), edge(cd.0, cd.1));
+                if ab.0 != ab.1
+                    && cd.0 != cd.1
+                    && ab != cd
+                    && !set.contains(&ab)
+                    && !set.contains(&cd)
+                {
+                    set.remove(&edges[x]);
+                    set.remove(&edges[y]);
+                    set.insert(ab);
+                    set.insert(cd);
+                    edges[x] = ab;
+                    edges[y] = cd;
+                }
+            }
+            let tmp = Self::from_edges(ids, set);
+            if k < 2 || tmp.stats().diameter.is_some() {
+                return tmp;
+            }
+        }
+    }
+
+    /// Watts-Strogatz small world: a ring lattice with `k` neighbours per node
+    /// whose edges are rewired to a random node with probability `p`
+    pub fn small_world(ids: &[String], k: usize, p: f64, seed: u64) -> Self {
+        assert!(
+            k >= 2,
+            "a ring lattice needs at least two neighbours per node"
+        );
+        let n = ids.len();
+        let rng = fastrand::Rng::with_seed(seed);
+        let mut set = BTreeSet::new();
+        for i in 0..n {
+            for j in 1..=k / 2 {
+                set.insert(edge(i, (i + j) % n));
+            }
+        }
+        for (a, b) in set.clone() {
+            if rng.f64() < p {
+                let c = rng.usize(..n);
+                if c != a && !set.contains(&edge(a, c)) {
+                    set.remove(&(a, b));
+                    set.insert(edge(a, c));
+                }
+            }
+        }
+        Self::from_edges(ids, set.into_iter().filter(|(a, b)| a != b))
+    }
+
+    /// Breadth-first spanning tree of this graph, minimizing the depth from `root`
+    pub fn spanning_tree(&self, root: &str) -> Self {
+        let ids: Vec<String> = self.neighbours.keys().cloned().collect();
+        let mut tmp = Self::empty(&ids);
+        let mut seen = BTreeSet::from([root]);
+        let mut queue = VecDeque::from([root]);
+        while let Some(curr) = queue.pop_front() {
+            for next in self.neighbours(curr) {
+                if seen.insert(next.as_str()) {
+                    tmp.link(curr, next);
+                    queue.push_back(next);
+                }
+            }
+        }
+        tmp
+    }
+
+    pub fn neighbours(&self, id: &str) -> impl Iterator<Item = &String> {
+        self.neighbours.get(id).into_iter().flatten()
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &String> {
+        self.neighbours.keys()
+    }
+
+    /// Hop distance from `root` to every reachable node
+    pub fn distances(&self, root: &str) -> BTreeMap<&str, usize> {
+        let mut dist = BTreeMap::new();
+        if let Some((root, _)) = self.neighbours.get_key_value(root) {
+            dist.insert(root.as_str(), 0);
+            let mut queue = VecDeque::from([root.as_str()]);
+            while let Some(curr) = queue.pop_front() {
+                let d = dist[curr];
+                for next in self.neighbours(curr) {
+                    dist.entry(next.as_str()).or_insert_with(|| {
+                        queue.push_back(next);
+                        d + 1
+                    });
+                }
+            }
+        }
+        dist
+    }
+
+    pub fn stats(&self) -> Stats {
+        let nodes = self.neighbours.len();
+        let fanouts: Vec<usize> = self.neighbours.values().map(|n| n.len()).collect();
+        let mut diameter = Some(0);
+        for id in self.nodes() {
+            let dist = self.distances(id);
+            diameter = match diameter {
+                Some(d) if dist.len() == nodes => dist.values().max().map(|m| d.max(*m)),
+                _ => None,
+            };
+        }
+        Stats {
+            nodes,
+            edges: fanouts.iter().sum::<usize>() / 2,
+            diameter,
+            min_fanout: fanouts.iter().copied().min().unwrap_or(0),
+            max_fanout: fanouts.iter().copied().max().unwrap_or(0),
+            mean_fanout: fanouts.iter().sum::<usize>() as f64 / nodes.max(1) as f64,
+        }
+    }
+}
+
+fn edge(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
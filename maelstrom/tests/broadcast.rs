@@ -0,0 +1,204 @@
+mod common;
+
+use std::{
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use common::Setup;
+use gossip_glomers::{nemesis::Lifecycle, storage::Storage};
+use serde_json::{json, Value};
+
+const BIN: &str = env!("CARGO_BIN_EXE_maelstrom-broadcast");
+
+/// Wait for a node to read `n` messages
+fn reads(setup: &mut Setup, node: &str, n: usize) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let read = setup.rpc(node, json!({"type": "read"})).unwrap();
+        if read["messages"].as_array().unwrap().len() == n {
+            break;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "{node} still has {}",
+            read["messages"]
+        );
+        sleep(Duration::from_millis(100));
+    }
+}
+
+#[test]
+fn restarted_node_keeps_messages_and_topology() {
+    let mut setup = Setup::new("broadcast-restart", BIN, 1, true);
+    setup.topology();
+    for message in [3, 1, 2] {
+        let body = json!({"type": "broadcast", "message": message});
+        setup.rpc("n0", body).unwrap();
+    }
+
+    setup.cluster.kill("n0");
+    setup.cluster.restart("n0");
+    let read = setup.rpc("n0", json!({"type": "read"})).unwrap();
+    // In the order they arrived, replayed from the log
+    assert_eq!(read["messages"], json!([3, 1, 2]));
+    let saved: Vec<String> = Storage::at(setup.state("n0"))
+        .unwrap()
+        .load("topology")
+        .unwrap()
+        .unwrap();
+    assert!(saved.is_empty());
+}
+
+#[test]
+fn node_losing_its_state_catches_up() {
+    let mut setup = Setup::new("broadcast-catch-up", BIN, 2, false);
+    setup.topology();
+    for message in 0..50 {
+        let body = json!({"type": "broadcast", "message": message});
+        setup.rpc("n0", body).unwrap();
+    }
+
+    // Without a state directory n1 comes back empty, and only reconciling
+    // with n0 brings the messages back
+    setup.cluster.kill("n1");
+    setup.cluster.restart("n1");
+    reads(&mut setup, "n1", 50);
+}
+
+#[test]
+fn acks_at_once_while_a_neighbour_is_down() {
+    let mut setup = Setup::new("broadcast-outbox", BIN, 2, true);
+    setup.topology();
+    setup.cluster.kill("n1");
+    for message in 0..20 {
+        let start = Instant::now();
+        let body = json!({"type": "broadcast", "message": message});
+        setup.rpc("n0", body).unwrap();
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+    // The messages queued for n1 reach it once back
+    setup.cluster.restart("n1");
+    reads(&mut setup, "n1", 20);
+}
+
+#[test]
+fn full_outbox_drops_the_oldest_messages() {
+    let env = [("BROADCAST_OUTBOX_LIMIT", "5"), ("BROADCAST_SYNC_MS", "0")];
+    let mut setup = Setup::with_env("broadcast-outbox-limit", BIN, 2, false, &env);
+    setup.topology();
+    setup.cluster.kill("n1");
+    for message in 0..20 {
+        let body = json!({"type": "broadcast", "message": message});
+        setup.rpc("n0", body).unwrap();
+    }
+    // Until every flood to n1 has timed out and been queued
+    sleep(Duration::from_secs(2));
+    setup.cluster.restart("n1");
+    reads(&mut setup, "n1", 5);
+    let read = setup.rpc("n1", json!({"type": "read"})).unwrap();
+    let mut messages: Vec<u64> = serde_json::from_value(read["messages"].clone()).unwrap();
+    messages.sort_unstable();
+    assert_eq!(messages, [15, 16, 17, 18, 19]);
+}
+
+#[test]
+fn batch_interval_past_the_longest_backoff_keeps_flushing() {
+    let env = [
+        ("BROADCAST_MODE", "batch"),
+        ("BROADCAST_INTERVAL_MS", "6000"),
+        ("BROADCAST_SYNC_MS", "0"),
+    ];
+    let mut setup = Setup::with_env("broadcast-long-interval", BIN, 2, false, &env);
+    setup.topology();
+    setup.cluster.kill("n1");
+    let body = json!({"type": "broadcast", "message": 1});
+    setup.rpc("n0", body).unwrap();
+    // Past the first flush to n1 timing out, the next one reaching it
+    sleep(Duration::from_secs(8));
+    setup.cluster.restart("n1");
+    reads(&mut setup, "n1", 1);
+}
+
+#[test]
+fn nodes_holding_colliding_messages_exchange_them() {
+    let mut setup = Setup::new("broadcast-collision", BIN, 2, false);
+    // Enough messages in common for the sync to send a table rather than
+    // the keys, before any node knows its neighbours
+    for message in 0..40 {
+        for node in ["n0", "n1"] {
+            let body = json!({"type": "broadcast", "message": message});
+            setup.rpc(node, body).unwrap();
+        }
+    }
+    // Their JSON forms have the same FNV-1a hash
+    let (a, b) = ("027bd23925db44f0", "3cbc054dc1af4102");
+    setup
+        .rpc("n0", json!({"type": "broadcast", "message": a}))
+        .unwrap();
+    setup
+        .rpc("n1", json!({"type": "broadcast", "message": b}))
+        .unwrap();
+    setup.topology();
+    for node in ["n0", "n1"] {
+        reads(&mut setup, node, 42);
+    }
+}
+
+#[test]
+fn dedups_json_messages_and_reads_incrementally() {
+    let mut setup = Setup::new("broadcast-cursor", BIN, 2, true);
+    setup.topology();
+    // Equal content, or the same id whatever the content, is one message,
+    // and equal content under another id another one
+    let bodies = [
+        json!({"message": {"user": "a", "text": "hi"}}),
+        json!({"message": "plain"}),
+        json!({"message": {"user": "a", "text": "hi"}}),
+        json!({"message": [1, 2], "id": "k"}),
+        json!({"message": [3], "id": "k"}),
+        json!({"message": "plain", "id": "j"}),
+    ];
+    for mut body in bodies {
+        body["type"] = json!("broadcast");
+        setup.rpc("n0", body).unwrap();
+    }
+    let expected = json!([{"user": "a", "text": "hi"}, "plain", [1, 2], "plain"]);
+    let read = setup.rpc("n0", json!({"type": "read"})).unwrap();
+    assert_eq!(read["messages"], expected);
+    assert!(read.get("cursor").is_none());
+    // n1 may have received them in another order
+    let sorted = |messages: &Value| {
+        let mut tmp: Vec<String> = messages
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m.to_string())
+            .collect();
+        tmp.sort();
+        tmp
+    };
+    reads(&mut setup, "n1", 4);
+    let read = setup.rpc("n1", json!({"type": "read"})).unwrap();
+    assert_eq!(sorted(&read["messages"]), sorted(&expected));
+
+    // Cursors are positions in the order of a single node
+    let read = setup.rpc("n0", json!({"type": "read", "after": 0}));
+    let read = read.unwrap();
+    assert_eq!(read["messages"], expected);
+    assert_eq!(read["cursor"], 4);
+    let body = json!({"type": "broadcast", "message": null});
+    setup.rpc("n0", body).unwrap();
+    let read = setup.rpc("n0", json!({"type": "read", "after": 4}));
+    let read = read.unwrap();
+    assert_eq!(read["messages"], json!([null]));
+    assert_eq!(read["cursor"], 5);
+
+    // Cursors still hold once the order is replayed after a restart
+    setup.cluster.kill("n0");
+    setup.cluster.restart("n0");
+    let read = setup.rpc("n0", json!({"type": "read", "after": 2}));
+    let read = read.unwrap();
+    assert_eq!(read["messages"], json!([[1, 2], "plain", null]));
+    assert_eq!(read["cursor"], 5);
+}
@@ -0,0 +1,86 @@
+// Each test binary uses only part of the helpers
+#![allow(dead_code)]
+
+use std::{fs, path::PathBuf, time::Duration};
+
+use gossip_glomers::{cluster::Processes, generator::Client, net, Err};
+use serde_json::{json, Value};
+
+/// Fresh temporary directory, removed once whatever uses it is gone
+pub struct Dir(pub PathBuf);
+
+impl Dir {
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("maelstrom-{name}-{}", std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        Self(dir)
+    }
+}
+
+impl Drop for Dir {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.0).ok();
+    }
+}
+
+/// Nodes of a binary logging to a fresh temporary directory, with a client
+pub struct Setup {
+    pub cluster: Processes,
+    pub client: Client,
+    // Dropped last, after the nodes are killed
+    dir: Dir,
+}
+
+impl Setup {
+    /// `n` nodes of `bin`, keeping their state across restarts if `durable`
+    pub fn new(name: &str, bin: &str, n: usize, durable: bool) -> Self {
+        Self::with_env(name, bin, n, durable, &[])
+    }
+
+    /// Nodes configured with environment variables
+    pub fn with_env(name: &str, bin: &str, n: usize, durable: bool, env: &[(&str, &str)]) -> Self {
+        let dir = Dir::new(name);
+        fs::create_dir_all(dir.0.join("logs")).unwrap();
+        let config = net::Config {
+            latency: Duration::from_millis(1),
+            trace: true,
+            ..Default::default()
+        };
+        let state = durable.then(|| dir.0.join("state"));
+        let logs = Some(dir.0.join("logs"));
+        let env = env.iter().map(|(k, v)| (k.to_string(), v.to_string()));
+        let cluster =
+            Processes::with_env(config, n, bin.into(), logs, state, env.collect()).unwrap();
+        let client = Client::new(&cluster.net, "c1");
+        Self {
+            cluster,
+            client,
+            dir,
+        }
+    }
+
+    /// Directory of a node's state
+    pub fn state(&self, node: &str) -> PathBuf {
+        self.dir.0.join("state").join(node)
+    }
+
+    pub fn rpc(&mut self, node: &str, body: Value) -> Result<Value, Err> {
+        self.client.rpc(node, body, Duration::from_secs(5))
+    }
+
+    /// Make every node a broadcast neighbour of every other one
+    pub fn topology(&mut self) {
+        let nodes = self.cluster.nodes.clone();
+        let topology: serde_json::Map<String, Value> = nodes
+            .iter()
+            .map(|node| {
+                let others: Vec<&String> = nodes.iter().filter(|n| *n != node).collect();
+                (node.clone(), json!(others))
+            })
+            .collect();
+        for node in &nodes {
+            let body = json!({"type": "topology", "topology": topology});
+            self.rpc(node, body).unwrap();
+        }
+    }
+}
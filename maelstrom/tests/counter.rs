@@ -0,0 +1,25 @@
+mod common;
+
+use common::Setup;
+use gossip_glomers::KV;
+use serde_json::json;
+
+const BIN: &str = env!("CARGO_BIN_EXE_maelstrom-counter");
+
+#[test]
+fn seq_kv_counter_reads_the_adds_of_other_nodes() {
+    let env = [("COUNTER_MODE", "seq-kv")];
+    let mut setup = Setup::with_env("counter-seq-kv", BIN, 2, false, &env);
+    setup.cluster.net.serve(KV::Seq);
+    setup.rpc("n1", json!({"type": "read"})).unwrap();
+    for delta in [5, -2] {
+        let body = json!({"type": "add", "delta": delta});
+        setup.rpc("n0", body).unwrap();
+    }
+    // n1 saw seq-kv before the adds, and reads them only once its own
+    // write moved it past them
+    for node in ["n1", "n0", "n1"] {
+        let read = setup.rpc(node, json!({"type": "read"})).unwrap();
+        assert_eq!(read["value"], 3, "{node}");
+    }
+}
@@ -0,0 +1,164 @@
+use std::fmt::Debug;
+
+use gossip_glomers::crdt::{Crdt, GCounter, GSet, LWWRegister, MVRegister, ORSet, PNCounter};
+use proptest::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+const NODES: [&str; 3] = ["n0", "n1", "n2"];
+
+#[derive(Debug, Clone)]
+enum Op {
+    /// Local update on a replica with an arbitrary value and flag
+    Update(usize, u8, bool),
+    /// Replica merges the state of another one
+    Sync(usize, usize),
+}
+
+fn ops() -> impl Strategy<Value = Vec<Op>> {
+    prop::collection::vec(
+        prop_oneof![
+            3 => (0..3usize, any::<u8>(), any::<bool>()).prop_map(|(r, v, f)| Op::Update(r, v, f)),
+            1 => (0..3usize, 0..3usize).prop_map(|(a, b)| Op::Sync(a, b)),
+        ],
+        0..40,
+    )
+}
+
+/// Replay a random history over three replicas sharing part of their causal past
+fn replicas<C: Crdt>(ops: &[Op], update: impl Fn(&mut C, &str, u64, u8, bool)) -> [C; 3] {
+    let mut states: [C; 3] = Default::default();
+    for (time, op) in ops.iter().enumerate() {
+        match *op {
+            Op::Update(r, v, f) => update(&mut states[r], NODES[r], time as u64 + 1, v, f),
+            Op::Sync(from, to) => {
+                let other = states[from].clone();
+                states[to].merge(&other);
+            }
+        }
+    }
+    states
+}
+
+fn merged<C: Crdt>(a: &C, b: &C) -> C {
+    let mut tmp = a.clone();
+    tmp.merge(b);
+    tmp
+}
+
+fn check_laws<C>([a, b, c]: [C; 3]) -> Result<(), TestCaseError>
+where
+    C: Crdt + PartialEq + Debug + Serialize + DeserializeOwned,
+{
+    // Commutativity
+    prop_assert_eq!(merged(&a, &b), merged(&b, &a));
+    // Associativity
+    prop_assert_eq!(merged(&merged(&a, &b), &c), merged(&a, &merged(&b, &c)));
+    // Idempotence
+    prop_assert_eq!(merged(&a, &a), a.clone());
+    prop_assert_eq!(merged(&merged(&a, &b), &b), merged(&a, &b));
+    // Delta brings the other replica to the same state as the full merge
+    prop_assert_eq!(merged(&b, &a.delta(&b)), merged(&b, &a));
+    prop_assert_eq!(merged(&c, &b.delta(&c)), merged(&c, &b));
+    // Serde round trip
+    let json = serde_json::to_string(&a).unwrap();
+    prop_assert_eq!(serde_json::from_str::<C>(&json).unwrap(), a);
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn g_counter(ops in ops()) {
+        check_laws(replicas(&ops, |s: &mut GCounter, node, _, v, _| s.increment(node, v as u64)))?;
+    }
+
+    #[test]
+    fn pn_counter(ops in ops()) {
+        let states = replicas(&ops, |s: &mut PNCounter, node, _, v, neg| {
+            s.add(node, if neg { -(v as i64) } else { v as i64 })
+        });
+        check_laws(states)?;
+    }
+
+    #[test]
+    fn g_set(ops in ops()) {
+        check_laws(replicas(&ops, |s: &mut GSet<u8>, _, _, v, _| { s.insert(v % 16); }))?;
+    }
+
+    #[test]
+    fn or_set(ops in ops()) {
+        let states = replicas(&ops, |s: &mut ORSet<u8>, node, _, v, remove| {
+            if remove { s.remove(&(v % 16)) } else { s.insert(node, v % 16) }
+        });
+        check_laws(states)?;
+    }
+
+    #[test]
+    fn lww_register(ops in ops()) {
+        let states = replicas(&ops, |s: &mut LWWRegister<u8>, node, time, v, stale| {
+            s.set(node, if stale { time / 2 } else { time }, v)
+        });
+        check_laws(states)?;
+    }
+
+    #[test]
+    fn mv_register(ops in ops()) {
+        check_laws(replicas(&ops, |s: &mut MVRegister<u8>, node, _, v, _| s.set(node, v)))?;
+    }
+
+    #[test]
+    fn pn_counter_value(deltas in prop::collection::vec((0..3usize, -100i64..100), 0..40)) {
+        let mut states: [PNCounter; 3] = Default::default();
+        for (r, d) in &deltas {
+            states[*r].add(NODES[*r], *d);
+        }
+        let total = states.iter().fold(PNCounter::new(), |acc, s| merged(&acc, s));
+        prop_assert_eq!(total.value(), deltas.iter().map(|(_, d)| d).sum::<i64>());
+    }
+}
+
+#[test]
+fn or_set_add_wins() {
+    let mut a = ORSet::new();
+    a.insert("n0", 1);
+    let mut b = a.clone();
+    // Concurrent remove and re-add
+    a.remove(&1);
+    b.insert("n1", 1);
+    a.merge(&b);
+    assert!(a.contains(&1));
+    b.remove(&1);
+    a.merge(&b);
+    assert!(!a.contains(&1));
+}
+
+#[test]
+fn mv_register_concurrent_writes() {
+    let mut a = MVRegister::new();
+    let mut b = MVRegister::new();
+    a.set("n0", 1);
+    b.set("n1", 2);
+    a.merge(&b);
+    assert_eq!(a.get().copied().collect::<Vec<_>>(), [1, 2]);
+    a.set("n0", 3);
+    b.merge(&a);
+    assert_eq!(b.get().copied().collect::<Vec<_>>(), [3]);
+}
+
+#[test]
+fn pn_counter_ignores_stale_states() {
+    let mut n0 = PNCounter::new();
+    n0.add("n0", 5);
+    let old = n0.clone();
+    n0.add("n0", -3);
+    n0.add("n0", 2);
+    // n1 hears of the newer state first, then of the older one
+    let mut n1 = PNCounter::new();
+    n1.merge(&n0);
+    n1.merge(&old);
+    assert_eq!(n1.value(), 4);
+    // n2 only ever talks to n1, and still learns the count of n0
+    let mut n2 = PNCounter::new();
+    n2.add("n2", -1);
+    n2.merge(&n1.delta(&n2));
+    assert_eq!(n2.value(), 3);
+}
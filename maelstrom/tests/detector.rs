@@ -0,0 +1,121 @@
+use std::{
+    sync::mpsc::sync_channel,
+    thread::{sleep, spawn},
+    time::{Duration, Instant},
+};
+
+use gossip_glomers::{
+    cluster::Cluster,
+    detector::{Change, Config, Detector},
+    generator::Client,
+    net, Msg, Node,
+};
+use parking_lot::Mutex;
+use serde_json::json;
+
+fn config() -> Config {
+    Config {
+        interval: Duration::from_millis(20),
+        threshold: 3.,
+        window: 10,
+        min_std_dev: Duration::from_millis(10),
+        acceptable_pause: Duration::from_millis(20),
+    }
+}
+
+#[test]
+fn phi_grows_with_silence() {
+    let peers = ["n1".to_owned()];
+    let detector = Detector::new(config(), &peers);
+    let mut prev = detector.phi("n1");
+    assert!(detector.is_alive("n1"));
+    for _ in 0..5 {
+        sleep(Duration::from_millis(15));
+        let phi = detector.phi("n1");
+        assert!(phi > prev, "{phi} after {prev}");
+        prev = phi;
+    }
+    assert!(!detector.is_alive("n1"));
+    // Unknown peers are trusted
+    assert_eq!(detector.phi("n2"), 0.);
+    assert!(detector.is_alive("n2"));
+}
+
+/// Node reporting its view of `n1` and the changes it was notified of
+fn watcher(node: Node) {
+    let detector = node.enable_detector(config());
+    let changes = Mutex::new(detector.subscribe());
+    node.run(|msg| {
+        let changes: Vec<(String, bool)> = changes
+            .lock()
+            .try_iter()
+            .map(|Change { peer, alive }| (peer, alive))
+            .collect();
+        let body = json!({
+            "type": "status_ok",
+            "alive": node.is_alive("n1"),
+            "changes": changes
+        });
+        node.reply(&msg, body);
+    });
+}
+
+#[test]
+fn cut_peers_are_suspected_then_trusted_again() {
+    let config = net::Config {
+        latency: Duration::from_millis(1),
+        ..Default::default()
+    };
+    let cluster = Cluster::new(config, 2, watcher).unwrap();
+    let mut client = Client::new(&cluster.net, "c1");
+    let mut changes = Vec::new();
+    let mut until = |alive: bool| {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let status = client.rpc("n0", json!({"type": "status"}), Duration::from_secs(1));
+            let status = status.unwrap();
+            changes.extend(status["changes"].as_array().unwrap().clone());
+            if status["alive"] == alive {
+                break;
+            }
+            assert!(Instant::now() < deadline, "n1 never alive={alive}");
+            sleep(Duration::from_millis(10));
+        }
+    };
+    // Heartbeats keep n1 trusted
+    sleep(Duration::from_millis(200));
+    until(true);
+
+    {
+        let mut faults = cluster.net.faults();
+        faults.nodes = cluster.nodes.iter().cloned().collect();
+        faults.cut.insert(("n1".to_owned(), "n0".to_owned()));
+    }
+    until(false);
+    cluster.net.faults().cut.clear();
+    until(true);
+    assert_eq!(changes, [json!(["n1", false]), json!(["n1", true])]);
+}
+
+#[test]
+fn run_returns_once_the_transport_closes() {
+    let (to_node, receiver) = sync_channel(16);
+    let (sender, _from_node) = sync_channel(16);
+    let body = json!({"type": "init", "msg_id": 1, "node_id": "n0", "node_ids": ["n0"]});
+    let init = Msg {
+        src: "c0".to_owned(),
+        dest: "n0".to_owned(),
+        body,
+    };
+    to_node.send(init).unwrap();
+    let (done, stopped) = sync_channel(1);
+    spawn(move || {
+        let node = Node::from_channels(receiver, sender);
+        node.enable_detector(config());
+        node.run(|_| {});
+        done.send(()).unwrap();
+    });
+    sleep(Duration::from_millis(50));
+    drop(to_node);
+    stopped.recv_timeout(Duration::from_secs(1)).unwrap();
+}
@@ -0,0 +1,128 @@
+use gossip_glomers::{
+    checker::{elle, kafka, linearizable},
+    edn::{self, Edn},
+    history::{History, Type},
+};
+use serde_json::json;
+
+#[test]
+fn reads_clojure_syntax() {
+    let text = r#"
+        ; comment
+        {:a/b? [1 -2 3N 1.5 -0.25M 1/4 ##Inf] , :s "q\"\né"
+         :c (\a \space \newline) :set #{:x} :n nil #_ :discarded :t true}
+        #jepsen.history.Op{:index 0, :type :invoke}
+        #inst "2024-01-01T00:00:00.000-00:00"
+        sym
+    "#;
+    let items = edn::parse(text).unwrap();
+    assert_eq!(items.len(), 4);
+    let map = &items[0];
+    assert_eq!(
+        map.get("a/b?"),
+        Some(&Edn::Vector(vec![
+            Edn::Int(1),
+            Edn::Int(-2),
+            Edn::Int(3),
+            Edn::Float(1.5),
+            Edn::Float(-0.25),
+            Edn::Float(0.25),
+            Edn::Float(f64::INFINITY),
+        ]))
+    );
+    assert_eq!(map.get("s"), Some(&Edn::Str("q\"\né".to_owned())));
+    assert_eq!(
+        map.get("c"),
+        Some(&Edn::List(vec![
+            Edn::Char('a'),
+            Edn::Char(' '),
+            Edn::Char('\n')
+        ]))
+    );
+    assert_eq!(map.get("set"), Some(&Edn::Set(vec![Edn::keyword("x")])));
+    assert_eq!(map.get("n"), Some(&Edn::Nil));
+    assert_eq!(map.get("t"), Some(&Edn::Bool(true)));
+    assert_eq!(items[1].get("type"), Some(&Edn::keyword("invoke")));
+    assert!(matches!(&items[2], Edn::Tagged(tag, _) if tag == "inst"));
+    assert_eq!(items[3], Edn::Symbol("sym".to_owned()));
+
+    // Printing reads back the same
+    for item in &items {
+        assert_eq!(&item.to_string().parse::<Edn>().unwrap(), item);
+    }
+    assert!("{:a}".parse::<Edn>().is_err());
+    assert!("[1 2".parse::<Edn>().unwrap_err().contains("missing ']'"));
+}
+
+#[test]
+fn imports_maelstrom_lin_kv_history() {
+    let text = r#"{:type :invoke, :f :write, :value [0 3], :time 1000, :process 0, :index 0}
+{:type :info, :f :start-partition, :value :majority, :time 1500, :process :nemesis, :index 1}
+{:type :ok, :f :write, :value [0 3], :time 2000, :process 0, :index 2}
+{:type :invoke, :f :cas, :value [0 [3 4]], :time 3000, :process 1, :index 3}
+{:type :ok, :f :cas, :value [0 [3 4]], :time 4000, :process 1, :index 4}
+{:type :invoke, :f :read, :value [0 nil], :time 5000, :process 0, :index 5}
+{:type :ok, :f :read, :value [0 3], :time 6000, :process 0, :index 6}"#;
+    let history = History::from_edn(text).unwrap();
+    assert_eq!(history.ops.len(), 6);
+    assert_eq!(history.ops[4].value, json!([0, null]));
+    assert_eq!(history.ops[4].index, 4);
+    // The read misses the cas that completed before it
+    let report = linearizable::check(&history);
+    assert!(!report.valid);
+
+    let fixed = text.replace("[0 3], :time 6000", "[0 4], :time 6000");
+    assert!(linearizable::check(&History::from_edn(&fixed).unwrap()).valid);
+}
+
+#[test]
+fn imports_kafka_micro_ops_and_txns() {
+    let text = r#"[#jepsen.history.Op{:index 0, :time 1, :type :invoke, :process 0, :f :send, :value [[:send 1 10]]}
+ #jepsen.history.Op{:index 1, :time 2, :type :ok, :process 0, :f :send, :value [[:send 1 [0 10]]]}
+ #jepsen.history.Op{:index 2, :time 3, :type :invoke, :process 1, :f :poll, :value [[:poll]]}
+ #jepsen.history.Op{:index 3, :time 4, :type :ok, :process 1, :f :poll, :value [[:poll {1 [[0 10]]}]], :final? true}
+ #jepsen.history.Op{:index 4, :time 5, :type :invoke, :process 2, :f :txn, :value [[:append 1 1] [:r 1 nil]]}
+ #jepsen.history.Op{:index 5, :time 6, :type :ok, :process 2, :f :txn, :value [[:append 1 1] [:r 1 [1]]]}]"#;
+    let history = History::from_edn(text).unwrap();
+    let values: Vec<_> = history.ops.iter().map(|op| op.value.clone()).collect();
+    assert_eq!(
+        values,
+        [
+            json!(["1", 10]),
+            json!(["1", 10, 0]),
+            json!(null),
+            json!({"1": [[0, 10]]}),
+            json!([["append", 1, 1], ["r", 1, null]]),
+            json!([["append", 1, 1], ["r", 1, [1]]]),
+        ]
+    );
+    assert!(history.ops[3].r#final);
+    assert!(kafka::check(&history).valid);
+    assert!(
+        elle::check(
+            &history,
+            elle::Workload::ListAppend,
+            elle::Model::Serializable
+        )
+        .valid
+    );
+}
+
+#[test]
+fn exported_histories_read_back() {
+    let mut history = History::new();
+    history.push(0, Type::Invoke, "send", json!(["3", 7]));
+    history.push(0, Type::Ok, "send", json!(["3", 7, 0]));
+    history.push(1, Type::Invoke, "txn", json!([["w", 2, 1], ["r", 2, null]]));
+    history.push(1, Type::Info, "txn", json!([["w", 2, 1], ["r", 2, null]]));
+    history.push_final(2, Type::Invoke, "read", json!(null));
+    history.push_final(2, Type::Ok, "read", json!([1, 2]));
+
+    let text = history.to_edn();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 6);
+    assert!(lines[1].contains(":f :send, :value [[:send 3 [0 7]]]"));
+    assert!(lines[2].contains(":value [[:w 2 1] [:r 2 nil]]"));
+    assert!(lines[5].ends_with(":value [1 2], :final? true}"));
+    assert_eq!(History::from_edn(&text).unwrap().ops, history.ops);
+}
@@ -0,0 +1,85 @@
+use gossip_glomers::{
+    checker::elle::{check, Kind, Model, Workload},
+    history::{History, Type},
+};
+use serde_json::{json, Value};
+
+/// Build a history of sequential transactions `(process, type, txn)`, the
+/// invocation value being derived by clearing the reads
+fn history(txns: &[(u64, Type, Value)]) -> History {
+    let mut history = History::new();
+    for (process, ty, txn) in txns {
+        let mut invoke = txn.clone();
+        for mop in invoke.as_array_mut().unwrap() {
+            if mop[0] == "r" {
+                mop[2] = Value::Null;
+            }
+        }
+        history.push(*process, Type::Invoke, "txn", invoke);
+        history.push(*process, *ty, "txn", txn.clone());
+    }
+    history
+}
+
+#[test]
+fn list_append_serial() {
+    use Type::*;
+    let h = history(&[
+        (0, Ok, json!([["append", 1, 1], ["append", 2, 1]])),
+        (1, Ok, json!([["r", 1, [1]], ["append", 1, 2]])),
+        (0, Ok, json!([["r", 1, [1, 2]], ["r", 2, [1]]])),
+    ]);
+    let report = check(&h, Workload::ListAppend, Model::Serializable);
+    assert!(report.valid, "{report:?}");
+    assert!(report.anomalies.is_empty());
+}
+
+#[test]
+fn list_append_anomalies() {
+    use Type::*;
+    let h = history(&[
+        // Aborted read
+        (0, Fail, json!([["append", 1, 1]])),
+        (1, Ok, json!([["r", 1, [1]]])),
+        // Write cycle: T2 before T3 on key 2, after it on key 3
+        (2, Ok, json!([["append", 2, 1], ["append", 3, 2]])),
+        (3, Ok, json!([["append", 2, 2], ["append", 3, 1]])),
+        (4, Ok, json!([["r", 2, [1, 2]], ["r", 3, [1, 2]]])),
+    ]);
+    let report = check(&h, Workload::ListAppend, Model::ReadCommitted);
+    assert!(!report.valid);
+    assert!(report.anomaly_types.contains(&Kind::G1a));
+    assert!(report.anomaly_types.contains(&Kind::G0));
+}
+
+#[test]
+fn list_append_write_skew() {
+    use Type::*;
+    // Each transaction misses the other's append: two anti-dependencies
+    let h = history(&[
+        (0, Ok, json!([["r", 1, []], ["append", 2, 1]])),
+        (1, Ok, json!([["r", 2, []], ["append", 1, 1]])),
+        (2, Ok, json!([["r", 1, [1]], ["r", 2, [1]]])),
+    ]);
+    let report = check(&h, Workload::ListAppend, Model::SnapshotIsolation);
+    assert!(report.valid);
+    assert_eq!(report.anomaly_types.iter().collect::<Vec<_>>(), [&Kind::G2]);
+    assert!(!check(&h, Workload::ListAppend, Model::Serializable).valid);
+}
+
+#[test]
+fn rw_register_anomalies() {
+    use Type::*;
+    let h = history(&[
+        (0, Ok, json!([["w", 1, 1], ["w", 1, 2]])),
+        // Intermediate read
+        (1, Ok, json!([["r", 1, 1]])),
+        // Lost update: both read the initial version and overwrite it
+        (2, Ok, json!([["r", 2, null], ["w", 2, 1]])),
+        (3, Ok, json!([["r", 2, null], ["w", 2, 2]])),
+    ]);
+    let report = check(&h, Workload::RwRegister, Model::Serializable);
+    assert!(!report.valid);
+    assert!(report.anomaly_types.contains(&Kind::G1b));
+    assert!(report.anomaly_types.contains(&Kind::G2));
+}
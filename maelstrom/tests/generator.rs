@@ -0,0 +1,146 @@
+use std::{
+    collections::BTreeSet,
+    sync::atomic::{AtomicU64, Ordering::SeqCst},
+    time::Duration,
+};
+
+use gossip_glomers::{
+    checker::{broadcast, unique_ids},
+    cluster::Cluster,
+    generator::{self, Client, Config, Workload},
+    history::History,
+    net::{self, Net},
+    Node, KV,
+};
+use parking_lot::Mutex;
+use serde_json::json;
+
+/// Run a workload against in-process nodes each running `main`
+fn cluster(config: &Config, n: usize, main: fn(Node)) -> History {
+    let net = net::Config {
+        latency: Duration::from_millis(5),
+        ..Default::default()
+    };
+    let cluster = Cluster::new(net, n, main).unwrap();
+    generator::run(&cluster.net, &cluster.nodes, config)
+}
+
+fn unique_ids(node: Node) {
+    let counter = AtomicU64::new(0);
+    node.run(|msg| {
+        let id = format!("{}-{}", node.id, counter.fetch_add(1, SeqCst));
+        node.reply(&msg, json!({"type": "generate_ok", "id": id}));
+    });
+}
+
+fn flood(node: Node) {
+    let seen = Mutex::new(BTreeSet::new());
+    node.run(|msg| match msg.body["type"].as_str().unwrap() {
+        "topology" => node.reply(&msg, json!({"type": "topology_ok"})),
+        "broadcast" => {
+            let message = msg.body["message"].as_u64().unwrap();
+            node.reply(&msg, json!({"type": "broadcast_ok"}));
+            if seen.lock().insert(message) {
+                for peer in node.other_ids() {
+                    let body = json!({"type": "broadcast", "message": message});
+                    node.rpc(peer.clone(), body).ok();
+                }
+            }
+        }
+        "read" => {
+            let messages = seen.lock().clone();
+            node.reply(&msg, json!({"type": "read_ok", "messages": messages}));
+        }
+        _ => {}
+    });
+}
+
+#[test]
+fn unique_ids_workload() {
+    let config = Config {
+        workload: Workload::UniqueIds,
+        rate: 200.,
+        concurrency: 4,
+        time_limit: Duration::from_millis(500),
+        ..Default::default()
+    };
+    let history = cluster(&config, 3, unique_ids);
+    let report = unique_ids::check(&history);
+    assert!(report.valid);
+    assert!(report.acknowledged > 10);
+}
+
+#[test]
+fn broadcast_workload() {
+    let config = Config {
+        workload: Workload::Broadcast,
+        rate: 100.,
+        concurrency: 3,
+        time_limit: Duration::from_millis(500),
+        recovery: Duration::from_millis(200),
+        ..Default::default()
+    };
+    let history = cluster(&config, 3, flood);
+    let report = broadcast::check(&history);
+    assert!(report.valid, "{report:?}");
+    assert_eq!(report.final_reads, 3);
+    assert!(report.acknowledged > 5);
+}
+
+#[test]
+fn kv_service() {
+    let net = Net::new(Default::default());
+    net.serve(KV::Lin);
+    let mut client = Client::new(&net, "c1");
+    let mut rpc = |body| client.rpc("lin-kv", body, Duration::from_secs(1));
+    assert!(rpc(json!({"type": "read", "key": 1})).is_err());
+    rpc(json!({"type": "cas", "key": 1, "from": 0, "to": 2, "create_if_not_exists": true}))
+        .unwrap();
+    assert!(rpc(json!({"type": "cas", "key": 1, "from": 0, "to": 3})).is_err());
+    rpc(json!({"type": "write", "key": 1, "value": 4})).unwrap();
+    let read = rpc(json!({"type": "read", "key": 1})).unwrap();
+    assert_eq!(read["value"], 4);
+}
+
+#[test]
+fn seq_kv_service_lags_behind_other_clients() {
+    let net = Net::new(Default::default());
+    net.serve(KV::Seq);
+    let timeout = Duration::from_secs(1);
+    let mut c1 = Client::new(&net, "c1");
+    let mut c2 = Client::new(&net, "c2");
+    c1.rpc(
+        "seq-kv",
+        json!({"type": "write", "key": 1, "value": 2}),
+        timeout,
+    )
+    .unwrap();
+    c2.rpc(
+        "seq-kv",
+        json!({"type": "write", "key": 2, "value": 3}),
+        timeout,
+    )
+    .unwrap();
+    c1.rpc(
+        "seq-kv",
+        json!({"type": "write", "key": 1, "value": 4}),
+        timeout,
+    )
+    .unwrap();
+    // c2 reads its own write, but not the later one of c1
+    let read = c2.rpc("seq-kv", json!({"type": "read", "key": 1}), timeout);
+    assert_eq!(read.unwrap()["value"], 2);
+    let read = c2.rpc("seq-kv", json!({"type": "read", "key": 2}), timeout);
+    assert_eq!(read.unwrap()["value"], 3);
+    // Until a write of its own orders it after them
+    c2.rpc(
+        "seq-kv",
+        json!({"type": "write", "key": 3, "value": 0}),
+        timeout,
+    )
+    .unwrap();
+    let read = c2.rpc("seq-kv", json!({"type": "read", "key": 1}), timeout);
+    assert_eq!(read.unwrap()["value"], 4);
+    let read = c1.rpc("seq-kv", json!({"type": "read", "key": 2}), timeout);
+    assert_eq!(read.unwrap()["value"], 3);
+}
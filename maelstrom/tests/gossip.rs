@@ -0,0 +1,169 @@
+use std::{
+    thread::{scope, sleep},
+    time::{Duration, Instant},
+};
+
+use gossip_glomers::{
+    cluster::Cluster,
+    crdt::GCounter,
+    generator::Client,
+    gossip::{Config, Gossip, Mode},
+    nemesis::Lifecycle,
+    net, Node,
+};
+use serde_json::json;
+
+const INTERVAL: Duration = Duration::from_millis(20);
+
+/// Grow-only counter replicated with `config`
+fn counter(node: Node, config: Config) {
+    let node = &node;
+    let gossip = &Gossip::<GCounter>::new(config);
+    scope(|s| {
+        gossip.start(s, node);
+        node.run(|msg| match msg.body["type"].as_str().unwrap() {
+            "gossip" => gossip.on_gossip(node, &msg),
+            "gossip_sync" => gossip.on_sync(node, &msg),
+            "add" => {
+                let delta = msg.body["delta"].as_u64().unwrap();
+                gossip.state().increment(&node.id, delta);
+                node.reply(&msg, json!({"type": "add_ok"}));
+            }
+            "read" => {
+                let value = gossip.state().value();
+                node.reply(&msg, json!({"type": "read_ok", "value": value}));
+            }
+            ty => unreachable!("msg type {ty}"),
+        });
+    });
+}
+
+fn rounds(mode: Mode) -> Config {
+    Config {
+        mode,
+        fanout: 1,
+        interval: INTERVAL,
+        anti_entropy: None,
+    }
+}
+
+fn push(node: Node) {
+    counter(node, rounds(Mode::Push))
+}
+
+fn pull(node: Node) {
+    counter(node, rounds(Mode::Pull))
+}
+
+fn push_pull(node: Node) {
+    counter(node, rounds(Mode::PushPull))
+}
+
+/// No gossip rounds, only digest exchanges
+fn anti_entropy(node: Node) {
+    let config = Config {
+        fanout: 0,
+        anti_entropy: Some(INTERVAL),
+        ..rounds(Mode::PushPull)
+    };
+    counter(node, config)
+}
+
+fn cluster(n: usize, main: fn(Node)) -> Cluster {
+    let config = net::Config {
+        latency: Duration::from_millis(1),
+        ..Default::default()
+    };
+    Cluster::new(config, n, main).unwrap()
+}
+
+fn add(cluster: &Cluster, node: &str, delta: u64) {
+    let mut client = Client::new(&cluster.net, "c1");
+    let body = json!({"type": "add", "delta": delta});
+    client.rpc(node, body, Duration::from_secs(1)).unwrap();
+}
+
+fn read(cluster: &Cluster, node: &str) -> u64 {
+    let mut client = Client::new(&cluster.net, "c2");
+    let read = client.rpc(node, json!({"type": "read"}), Duration::from_secs(1));
+    read.unwrap()["value"].as_u64().unwrap()
+}
+
+/// Wait for every node to read `value`
+fn converges(cluster: &Cluster, value: u64) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    for node in &cluster.nodes {
+        while read(cluster, node) != value {
+            assert!(
+                Instant::now() < deadline,
+                "{node} reads {}",
+                read(cluster, node)
+            );
+            sleep(INTERVAL);
+        }
+    }
+}
+
+#[test]
+fn every_mode_converges() {
+    for main in [push, pull, push_pull, anti_entropy] {
+        let cluster = cluster(5, main);
+        for (i, node) in cluster.nodes.iter().enumerate() {
+            add(&cluster, node, i as u64 + 1);
+        }
+        converges(&cluster, 15);
+    }
+}
+
+#[test]
+fn lost_pull_replies_are_sent_again() {
+    // Without anti-entropy, only acknowledgements make up for the drops
+    let cluster = cluster(3, pull);
+    cluster.net.faults().nodes = cluster.nodes.iter().cloned().collect();
+    cluster.net.faults().drop = 0.3;
+    for (i, node) in cluster.nodes.iter().enumerate() {
+        add(&cluster, node, i as u64 + 1);
+    }
+    converges(&cluster, 6);
+}
+
+#[test]
+fn partitioned_nodes_catch_up_once_healed() {
+    let cluster = cluster(3, push_pull);
+    {
+        let mut faults = cluster.net.faults();
+        faults.nodes = cluster.nodes.iter().cloned().collect();
+        for other in ["n1", "n2"] {
+            faults.cut.insert(("n0".to_owned(), other.to_owned()));
+            faults.cut.insert((other.to_owned(), "n0".to_owned()));
+        }
+    }
+    add(&cluster, "n0", 1);
+    add(&cluster, "n1", 2);
+    sleep(INTERVAL * 10);
+    assert_eq!(read(&cluster, "n0"), 1);
+    assert_eq!(read(&cluster, "n2"), 2);
+
+    cluster.net.faults().cut.clear();
+    converges(&cluster, 3);
+}
+
+#[test]
+fn killed_nodes_stop_gossiping() {
+    let config = net::Config {
+        latency: Duration::from_millis(1),
+        trace: true,
+        ..Default::default()
+    };
+    let cluster = Cluster::new(config, 3, push_pull).unwrap();
+    cluster.kill("n0");
+    // Let rounds started before the kill finish
+    sleep(INTERVAL * 5);
+    let sent = || {
+        let trace = cluster.net.trace();
+        trace.iter().filter(|e| e.msg.src == "n0").count()
+    };
+    let before = sent();
+    sleep(INTERVAL * 10);
+    assert_eq!(sent(), before);
+}
@@ -0,0 +1,44 @@
+use std::collections::BTreeSet;
+
+use gossip_glomers::iblt::Iblt;
+
+fn table(keys: impl IntoIterator<Item = u64>, cells: usize) -> Iblt {
+    let mut tmp = Iblt::new(cells);
+    for key in keys {
+        tmp.insert(key);
+    }
+    tmp
+}
+
+#[test]
+fn decodes_both_sides_of_a_difference() {
+    // Large sets differing by a few keys each way
+    let ours: BTreeSet<u64> = (0..10_000).filter(|k| k % 1000 != 7).collect();
+    let theirs: BTreeSet<u64> = (5..10_005).collect();
+    let mut diff = table(ours.iter().copied(), 80);
+    diff.subtract(&table(theirs.iter().copied(), 80));
+    let (mut only_ours, mut only_theirs) = diff.decode().unwrap();
+    only_ours.sort();
+    only_theirs.sort();
+    assert_eq!(
+        only_ours,
+        ours.difference(&theirs).copied().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        only_theirs,
+        theirs.difference(&ours).copied().collect::<Vec<_>>()
+    );
+    assert_eq!(only_ours.len() + only_theirs.len(), 20);
+
+    let mut same = table(ours.iter().copied(), 80);
+    same.subtract(&table(ours.iter().copied(), 80));
+    assert_eq!(same.decode(), Some((vec![], vec![])));
+}
+
+#[test]
+fn too_small_tables_fail_to_decode() {
+    let mut diff = table(0..500, 30);
+    diff.subtract(&table(250..300, 30));
+    assert_eq!(diff.decode(), None);
+    assert_eq!(Iblt::new(31).len(), 33);
+}
@@ -0,0 +1,55 @@
+use gossip_glomers::ids::{Snowflake, Ulid, SNOWFLAKE_EPOCH_MS};
+
+#[test]
+fn snowflakes_keep_increasing_when_the_clock_goes_back() {
+    let mut gen = Snowflake::new(5).unwrap();
+    let now = SNOWFLAKE_EPOCH_MS + 1_000_000;
+    // The clock stalls, then goes back by a second, then catches up
+    let times = (0..5000)
+        .map(|_| now)
+        .chain((0..100).map(|_| now - 1000))
+        .chain([now + 10]);
+    let ids: Vec<u64> = times.map(|t| gen.next(t)).collect();
+    assert!(ids.windows(2).all(|w| w[0] < w[1]));
+    // Past 4096 ids in a millisecond, the next one is borrowed
+    assert_eq!(ids[0] >> 22, 1_000_000);
+    assert_eq!(ids[4096] >> 22, 1_000_001);
+    assert_eq!(ids[0] >> 12 & 1023, 5);
+    assert_eq!(*ids.last().unwrap() >> 22, 1_000_010);
+    assert!(Snowflake::new(1024).is_err());
+}
+
+#[test]
+fn resumed_clocks_skip_the_lease() {
+    let mut gen = Snowflake::new(0).unwrap();
+    let now = SNOWFLAKE_EPOCH_MS + 1_000_000;
+    let before = gen.next(now);
+    // Restarted with a clock a minute late
+    let mut gen = Snowflake::new(0).unwrap();
+    gen.clock.resume(now + 1000);
+    let after = gen.next(now - 60_000);
+    assert!(after > before);
+    assert_eq!(after >> 22, 1_001_001);
+}
+
+#[test]
+fn ulids_sort_as_strings_and_differ_across_nodes() {
+    let (mut a, mut b) = (Ulid::new(1).unwrap(), Ulid::new(2).unwrap());
+    let now = 1_700_000_000_000;
+    let mut ids = Vec::new();
+    for t in [now, now, now - 5, now + 1] {
+        ids.push(Ulid::encode(a.next(t)));
+        ids.push(Ulid::encode(b.next(t)));
+    }
+    assert!(ids.iter().all(|id| id.len() == 26));
+    let mut ours: Vec<&String> = ids.iter().step_by(2).collect();
+    assert!(ours.windows(2).all(|w| w[0] < w[1]));
+    ours.sort();
+    ours.dedup();
+    assert_eq!(ours.len(), 4);
+    assert_ne!(ids[0], ids[1]);
+    // The 48-bit timestamp takes the first 10 digits
+    assert_eq!(&ids[0][..10], &ids[1][..10]);
+    assert_eq!(Ulid::encode(0), "0".repeat(26));
+    assert_eq!(Ulid::encode(u128::MAX), format!("7{}", "Z".repeat(25)));
+}
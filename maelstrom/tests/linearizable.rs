@@ -0,0 +1,122 @@
+mod common;
+
+use std::time::Duration;
+
+use common::Setup;
+use gossip_glomers::{
+    checker::linearizable::check,
+    generator::{self, Workload},
+    history::{History, Type},
+};
+use serde_json::{json, Value};
+
+/// Build a history from `(process, type, f, value)` events
+fn history(events: &[(u64, Type, &str, Value)]) -> History {
+    let mut history = History::new();
+    for (process, ty, f, value) in events {
+        history.push(*process, *ty, f, value.clone());
+    }
+    history
+}
+
+#[test]
+fn concurrent_write_and_read() {
+    use Type::*;
+    let h = history(&[
+        (0, Invoke, "write", json!([1, 1])),
+        (0, Ok, "write", json!([1, 1])),
+        (0, Invoke, "write", json!([1, 2])),
+        (1, Invoke, "read", json!([1, null])),
+        // The read may see either value while the write is in flight
+        (1, Ok, "read", json!([1, 1])),
+        (0, Ok, "write", json!([1, 2])),
+        (1, Invoke, "read", json!([1, null])),
+        (1, Ok, "read", json!([1, 2])),
+    ]);
+    assert!(check(&h).valid);
+}
+
+#[test]
+fn stale_read() {
+    use Type::*;
+    let h = history(&[
+        (0, Invoke, "write", json!([1, 1])),
+        (0, Ok, "write", json!([1, 1])),
+        (2, Invoke, "write", json!([2, 5])),
+        (2, Ok, "write", json!([2, 5])),
+        (0, Invoke, "write", json!([1, 2])),
+        (0, Ok, "write", json!([1, 2])),
+        (1, Invoke, "read", json!([1, null])),
+        (1, Ok, "read", json!([1, 1])),
+        (2, Invoke, "read", json!([2, null])),
+        (2, Ok, "read", json!([2, 5])),
+    ]);
+    let report = check(&h);
+    assert!(!report.valid);
+    assert_eq!(report.keys, 2);
+    assert_eq!(report.failures.len(), 1);
+    let failure = &report.failures[0];
+    assert_eq!(failure.key, json!(1));
+    // The first write is irrelevant to the violation
+    let indices: Vec<usize> = failure.ops.iter().map(|op| op.index).collect();
+    assert_eq!(indices, [4, 5, 6, 7]);
+}
+
+#[test]
+fn indeterminate_cas() {
+    use Type::*;
+    let h = history(&[
+        (0, Invoke, "write", json!([1, 1])),
+        (0, Ok, "write", json!([1, 1])),
+        (0, Invoke, "cas", json!([1, [1, 2]])),
+        (0, Info, "cas", json!([1, [1, 2]])),
+        (1, Invoke, "cas", json!([1, [3, 4]])),
+        (1, Fail, "cas", json!([1, [3, 4]])),
+        (2, Invoke, "read", json!([1, null])),
+        (2, Ok, "read", json!([1, 2])),
+        (2, Invoke, "read", json!([1, null])),
+        (2, Ok, "read", json!([1, 2])),
+    ]);
+    assert!(check(&h).valid);
+
+    // A value never written can't be read
+    let mut h = h;
+    h.push(2, Invoke, "read", json!([1, null]));
+    h.push(2, Ok, "read", json!([1, 4]));
+    assert!(!check(&h).valid);
+}
+
+#[test]
+fn missing_key() {
+    use Type::*;
+    let h = history(&[
+        (0, Invoke, "cas", json!([1, [null, 2]])),
+        (0, Ok, "cas", json!([1, [null, 2]])),
+    ]);
+    assert!(!check(&h).valid);
+}
+
+#[test]
+fn raft_cluster_is_linearizable() {
+    let bin = env!("CARGO_BIN_EXE_maelstrom-raft");
+    let setup = Setup::new("raft-lin", bin, 3, false);
+    let cluster = &setup.cluster;
+    let config = generator::Config {
+        workload: Workload::LinKv,
+        rate: 50.,
+        concurrency: 4,
+        time_limit: Duration::from_secs(5),
+        key_count: 2,
+        ..Default::default()
+    };
+    let history = generator::run(&cluster.net, &cluster.nodes, &config);
+    let report = check(&history);
+    assert!(report.valid, "{:?}", report.failures);
+    let ok = history
+        .pairs()
+        .iter()
+        .filter(|p| p.ty() == Type::Ok)
+        .count();
+    // Requests are only refused until a leader is elected
+    assert!(ok > 50, "{ok} operations succeeded");
+}
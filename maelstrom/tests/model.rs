@@ -0,0 +1,132 @@
+use gossip_glomers::model::{check, Config, Invariant, Out, Process, State};
+
+/// Raft-style leader election, granting at most one vote per term unless
+/// `forgetful`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Election {
+    n: usize,
+    forgetful: bool,
+    term: u64,
+    voted_for: Option<usize>,
+    votes: usize,
+    leader: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Msg {
+    RequestVote(u64),
+    Vote(u64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Timeout;
+
+impl Process for Election {
+    type Msg = Msg;
+    type Timer = Timeout;
+
+    fn on_start(&mut self, _: usize, out: &mut Out<Self>) {
+        out.set_timer(Timeout);
+    }
+
+    fn on_msg(&mut self, id: usize, src: usize, msg: Msg, out: &mut Out<Self>) {
+        match msg {
+            Msg::RequestVote(term) => {
+                if term > self.term {
+                    (self.term, self.voted_for, self.leader) = (term, None, false);
+                }
+                let free = self.voted_for.is_none() || self.voted_for == Some(src);
+                if term == self.term && (free || self.forgetful) {
+                    self.voted_for = Some(src);
+                    out.send(src, Msg::Vote(term));
+                }
+            }
+            Msg::Vote(term) if term == self.term && self.voted_for == Some(id) => {
+                self.votes += 1;
+                self.leader = self.votes > self.n / 2;
+            }
+            Msg::Vote(_) => {}
+        }
+    }
+
+    fn on_timer(&mut self, id: usize, _: Timeout, out: &mut Out<Self>) {
+        if self.term >= 2 {
+            // Bound the number of elections
+            return;
+        }
+        (self.term, self.voted_for, self.votes, self.leader) = (self.term + 1, Some(id), 1, false);
+        for peer in (0..self.n).filter(|p| *p != id) {
+            out.send(peer, Msg::RequestVote(self.term));
+        }
+        out.set_timer(Timeout);
+    }
+}
+
+fn cluster(n: usize, forgetful: bool) -> Vec<Election> {
+    let proc = Election {
+        n,
+        forgetful,
+        term: 0,
+        voted_for: None,
+        votes: 0,
+        leader: false,
+    };
+    vec![proc; n]
+}
+
+fn one_leader_per_term(state: &State<Election>) -> bool {
+    let leaders: Vec<u64> = state
+        .procs
+        .iter()
+        .filter(|p| p.leader)
+        .map(|p| p.term)
+        .collect();
+    leaders
+        .iter()
+        .enumerate()
+        .all(|(i, t)| !leaders[i + 1..].contains(t))
+}
+
+const INVARIANTS: &[Invariant<Election>] = &[Invariant {
+    name: "one leader per term",
+    holds: one_leader_per_term,
+}];
+
+#[test]
+fn correct_election() {
+    let config = Config {
+        depth: 8,
+        lossy: true,
+        ..Default::default()
+    };
+    let stats = check(cluster(3, false), &config, INVARIANTS).unwrap();
+    assert!(stats.complete);
+    assert_eq!(stats.depth, 8);
+    assert!(stats.states > 1000);
+}
+
+#[test]
+fn double_vote_counterexample() {
+    let config = Config {
+        depth: 8,
+        ..Default::default()
+    };
+    let counterexample = check(cluster(3, true), &config, INVARIANTS).unwrap_err();
+    assert_eq!(counterexample.invariant, "one leader per term");
+    // Node 0 wins term 1, then votes again for node 2 in the same term
+    assert_eq!(counterexample.steps.len(), 6);
+    let printed = counterexample.to_string();
+    assert!(printed.contains("  6. deliver 0 -> 2 Vote(1)"), "{printed}");
+}
+
+#[test]
+fn state_budget() {
+    let config = Config {
+        depth: 20,
+        max_states: 100,
+        ..Default::default()
+    };
+    let stats = check(cluster(3, false), &config, INVARIANTS).unwrap();
+    assert!(!stats.complete);
+    assert_eq!(stats.states, 100);
+}
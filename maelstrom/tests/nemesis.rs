@@ -0,0 +1,149 @@
+use std::{
+    collections::BTreeSet,
+    sync::atomic::{AtomicU64, Ordering::SeqCst},
+    time::Duration,
+};
+
+use gossip_glomers::{
+    cluster::Cluster,
+    generator::Client,
+    nemesis::{apply, Config, Fault, Kind, Lifecycle, Plan},
+    net::Net,
+    Msg, Node,
+};
+use serde_json::json;
+
+fn ids(n: usize) -> Vec<String> {
+    (0..n).map(|i| format!("n{i}")).collect()
+}
+
+/// Lifecycle for networks without processes to kill
+struct Nothing;
+
+impl Lifecycle for Nothing {
+    fn kill(&self, _: &str) {}
+    fn restart(&self, _: &str) {}
+}
+
+#[test]
+fn reproducible_plan() {
+    let config = Config {
+        kinds: "partition,majority,bridge,drop,duplicate,reorder,latency,pause,kill"
+            .split(',')
+            .map(|k| k.parse().unwrap())
+            .collect(),
+        interval: Duration::from_secs(5),
+        time_limit: Duration::from_secs(60),
+        seed: 7,
+        ..Default::default()
+    };
+    let nodes = ids(5);
+    let plan = Plan::generate(&config, &nodes);
+    assert_eq!(plan, Plan::generate(&config, &nodes));
+    let json = serde_json::to_string(&plan).unwrap();
+    assert_eq!(plan, serde_json::from_str(&json).unwrap());
+
+    assert_eq!(plan.steps.len(), 12);
+    assert!(plan.steps.windows(2).all(|w| w[0].at < w[1].at));
+    assert!(plan.steps.iter().all(|s| s.at <= 60_000));
+    for step in &plan.steps {
+        if let Fault::Partition { groups, bridge } = &step.fault {
+            let mut all: BTreeSet<&String> = groups.iter().flatten().collect();
+            all.extend(bridge);
+            assert_eq!(all.len(), 5);
+        }
+    }
+    assert!("flood".parse::<Kind>().is_err());
+}
+
+/// Send from `src` and report whether `dest` got the message in time
+fn delivered(net: &Net, src: &str, dest: &str) -> bool {
+    let (_, sender) = net.join(src);
+    let (receiver, _) = net.join(dest);
+    let msg = Msg {
+        src: src.to_owned(),
+        dest: dest.to_owned(),
+        body: json!({"type": "ping"}),
+    };
+    sender.send(msg).unwrap();
+    receiver.recv_timeout(Duration::from_millis(100)).is_ok()
+}
+
+#[test]
+fn partition_and_pause() {
+    let net = Net::new(Default::default());
+    net.faults().nodes = ids(5).into_iter().collect();
+    let groups = vec![ids(2), ids(5)[2..].to_vec()];
+    apply(
+        &Fault::Partition {
+            groups,
+            bridge: None,
+        },
+        &net,
+        &Nothing,
+    );
+    assert!(delivered(&net, "n0", "n1"));
+    assert!(!delivered(&net, "n0", "n2"));
+    assert!(!delivered(&net, "n3", "n1"));
+    // Clients are not affected
+    assert!(delivered(&net, "c1", "n3"));
+
+    apply(&Fault::Heal, &net, &Nothing);
+    assert!(delivered(&net, "n0", "n2"));
+
+    let node = "n4".to_owned();
+    apply(&Fault::Pause { node: node.clone() }, &net, &Nothing);
+    let (receiver, _) = net.join("n4");
+    let (_, sender) = net.join("n3");
+    let body = json!({"type": "ping"});
+    let msg = Msg {
+        src: "n3".to_owned(),
+        dest: "n4".to_owned(),
+        body,
+    };
+    sender.send(msg).unwrap();
+    assert!(receiver.recv_timeout(Duration::from_millis(100)).is_err());
+    apply(&Fault::Resume { node }, &net, &Nothing);
+    assert!(receiver.recv_timeout(Duration::from_millis(100)).is_ok());
+}
+
+fn counter(node: Node) {
+    let count = AtomicU64::new(0);
+    node.run(|msg| {
+        let count = count.fetch_add(1, SeqCst);
+        node.reply(&msg, json!({"type": "count_ok", "count": count}));
+    });
+}
+
+#[test]
+fn kill_and_restart() {
+    let cluster = Cluster::new(Default::default(), 2, counter).unwrap();
+    let mut client = Client::new(&cluster.net, "c1");
+    let mut count = |node: &str| {
+        client
+            .rpc(node, json!({"type": "count"}), Duration::from_millis(200))
+            .map(|body| body["count"].as_u64().unwrap())
+    };
+    assert_eq!(count("n0").unwrap(), 0);
+    assert_eq!(count("n0").unwrap(), 1);
+
+    apply(
+        &Fault::Kill {
+            node: "n0".to_owned(),
+        },
+        &cluster.net,
+        &cluster,
+    );
+    assert!(count("n0").is_err());
+    assert_eq!(count("n1").unwrap(), 0);
+
+    apply(
+        &Fault::Restart {
+            node: "n0".to_owned(),
+        },
+        &cluster.net,
+        &cluster,
+    );
+    // State does not survive the crash
+    assert_eq!(count("n0").unwrap(), 0);
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use std::time::Duration;
+
+use common::Setup;
+use gossip_glomers::{generator::Client, nemesis::Lifecycle};
+use serde_json::{json, Value};
+
+const BIN: &str = env!("CARGO_BIN_EXE_maelstrom-raft");
+
+/// Log entry of `term` holding a write of `value`
+fn entry(term: u64, value: u64) -> Value {
+    let write = json!({"type": "write", "key": 1, "value": value});
+    json!([term, {"src": "c1", "dest": "n1", "body": write}])
+}
+
+/// Whether n0 accepts entries from n1 as the leader of `term`
+fn append(leader: &mut Client, term: u64, prev: (u64, u64), entries: &[Value]) -> bool {
+    let body = json!({
+        "type": "append_entries",
+        "term": term,
+        "leader_id": "n1",
+        "prev_log_index": prev.0,
+        "prev_log_term": prev.1,
+        "entries": entries,
+        "leader_commit": 0,
+    });
+    let reply = leader.rpc("n0", body, Duration::from_secs(5)).unwrap();
+    reply["success"].as_bool().unwrap()
+}
+
+#[test]
+fn follower_checks_appended_entries_against_the_leader_log() {
+    let setup = Setup::new("raft-append", BIN, 3, false);
+    // n0 follows whatever term we play n1 in, n2 staying out of the way
+    setup.cluster.kill("n1");
+    setup.cluster.kill("n2");
+    let leader = &mut Client::new(&setup.cluster.net, "n1");
+
+    assert!(append(leader, 100, (1, 0), &[entry(100, 1), entry(100, 2)]));
+    // A leader of an older term is refused
+    assert!(!append(leader, 99, (1, 0), &[]));
+    // A delayed copy of the first append keeps the entries following it
+    assert!(append(leader, 100, (1, 0), &[entry(100, 1)]));
+    assert!(append(leader, 100, (3, 100), &[]));
+    // The previous entry matches by its own term, not the current one
+    assert!(append(leader, 101, (3, 100), &[entry(101, 3)]));
+    assert!(!append(leader, 101, (4, 100), &[]));
+}
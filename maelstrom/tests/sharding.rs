@@ -0,0 +1,80 @@
+use std::collections::BTreeSet;
+
+use gossip_glomers::sharding::Ring;
+
+fn ids(n: usize) -> Vec<String> {
+    (0..n).map(|i| format!("n{i}")).collect()
+}
+
+fn keys() -> impl Iterator<Item = String> {
+    (0..2000).map(|i| format!("k{i}"))
+}
+
+#[test]
+fn replicas_are_distinct_and_start_with_the_owner() {
+    let ring = Ring::new(&ids(5), 64);
+    for key in keys() {
+        let owner = ring.owner(&key).unwrap();
+        for n in [1, 3, 5, 8] {
+            let replicas = ring.replicas(&key, n);
+            assert_eq!(replicas.len(), n.min(5));
+            assert_eq!(replicas[0], owner);
+            let distinct: BTreeSet<_> = replicas.iter().collect();
+            assert_eq!(distinct.len(), replicas.len(), "{key}: {replicas:?}");
+        }
+    }
+    assert_eq!(Ring::new(&ids(0), 64).owner("k0"), None);
+}
+
+#[test]
+fn adding_a_node_only_moves_keys_to_it() {
+    let old = Ring::new(&ids(4), 64);
+    let mut new = old.clone();
+    new.add("n4");
+    let moves = old.rebalance(&new, 1);
+    assert!(!moves.is_empty());
+    assert!(moves.iter().all(|m| m.to == ["n4"]), "{moves:?}");
+    for key in keys() {
+        let (before, after) = (old.owner(&key).unwrap(), new.owner(&key).unwrap());
+        let moved = moves.iter().any(|m| m.contains(&key));
+        assert_eq!(moved, before != after, "{key}");
+        assert!(before == after || after == "n4");
+    }
+}
+
+#[test]
+fn removing_a_node_only_moves_its_keys() {
+    let old = Ring::new(&ids(5), 64);
+    let mut new = old.clone();
+    new.remove("n2");
+    let moves = old.rebalance(&new, 2);
+    // With two replicas, every arc n2 held gets a new replica
+    assert!(moves.iter().all(|m| m.from.contains(&"n2".to_owned())));
+    assert!(moves.iter().all(|m| !m.to.contains(&"n2".to_owned())));
+    for key in keys() {
+        if old.owner(&key).unwrap() != "n2" {
+            assert_eq!(old.owner(&key), new.owner(&key));
+        }
+    }
+    assert!(old.rebalance(&old, 2).is_empty());
+}
+
+#[test]
+fn virtual_nodes_balance_the_load() {
+    let ring = Ring::new(&ids(5), 64);
+    let load = ring.load();
+    assert_eq!(load.len(), 5);
+    let fair = u64::MAX as f64 / 5.;
+    for (node, share) in &load {
+        let ratio = *share as f64 / fair;
+        assert!(
+            (0.5..2.).contains(&ratio),
+            "{node} owns {ratio} of its share"
+        );
+    }
+    let mut owned = [0; 5];
+    for key in keys() {
+        owned[ring.owner(&key).unwrap()[1..].parse::<usize>().unwrap()] += 1;
+    }
+    assert!(owned.iter().all(|n| (200..800).contains(n)), "{owned:?}");
+}
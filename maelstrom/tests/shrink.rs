@@ -0,0 +1,156 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering::SeqCst},
+    time::Duration,
+};
+
+use gossip_glomers::{
+    checker::unique_ids,
+    cluster::Cluster,
+    generator::{self, Request, Workload},
+    history::Type,
+    nemesis::{Fault, Plan, Step},
+    net,
+    shrink::{ddmin, replay, shrink, span, window, Case},
+    Node,
+};
+use serde_json::{json, Value};
+
+#[test]
+fn minimal_subset() {
+    let items: Vec<u32> = (0..40).collect();
+    let mut runs = 0;
+    let min = ddmin(&items, |s| {
+        runs += 1;
+        s.contains(&3) && s.contains(&17) && s.contains(&31)
+    });
+    assert_eq!(min, [3, 17, 31]);
+    assert!(runs < 150);
+    assert_eq!(ddmin(&items, |_| true), Vec::<u32>::new());
+}
+
+#[test]
+fn faults_go_with_their_end() {
+    let step = |at, fault| Step { at, fault };
+    let node = || "n1".to_owned();
+    let plan = Plan {
+        seed: 0,
+        steps: vec![
+            step(10, Fault::Drop { p: 0.5 }),
+            step(20, Fault::Drop { p: 0. }),
+            step(30, Fault::Kill { node: node() }),
+            step(40, Fault::Restart { node: node() }),
+        ],
+    };
+    let requests = (0..10)
+        .map(|i| Request {
+            client: 0,
+            at: i * 10,
+            f: if i == 6 { "read" } else { "write" }.to_owned(),
+            value: Value::Null,
+        })
+        .collect();
+    let case = Case { plan, requests };
+    let min = shrink(&case, |case| {
+        let kill = case
+            .plan
+            .steps
+            .iter()
+            .any(|s| matches!(s.fault, Fault::Kill { .. }));
+        kill && case.requests.iter().any(|r| r.f == "read")
+    });
+    assert_eq!(min.plan.steps, case.plan.steps[2..]);
+    assert_eq!(min.requests, case.requests[6..7]);
+}
+
+/// Unique ids forgetting their counter on restart
+fn forgetful(node: Node) {
+    let counter = AtomicU64::new(0);
+    node.run(|msg| {
+        let id = format!("{}-{}", node.id, counter.fetch_add(1, SeqCst));
+        node.reply(&msg, json!({"type": "generate_ok", "id": id}));
+    });
+}
+
+#[test]
+fn shrink_crash_restart_duplicates() {
+    let config = generator::Config {
+        workload: Workload::UniqueIds,
+        rate: 100.,
+        concurrency: 3,
+        time_limit: Duration::from_millis(300),
+        timeout: Duration::from_millis(100),
+        ..Default::default()
+    };
+    let net = net::Config {
+        trace: true,
+        ..Default::default()
+    };
+    let run = |case: &Case| {
+        let cluster = Cluster::new(net.clone(), 3, forgetful).unwrap();
+        let history = replay(&cluster.net, &cluster.nodes, &cluster, &config, case);
+        (unique_ids::check(&history), history, cluster.net.trace())
+    };
+    let node = |i: usize| format!("n{i}");
+    let steps = [
+        (
+            20,
+            Fault::Partition {
+                groups: vec![vec![node(0)], vec![node(1), node(2)]],
+                bridge: None,
+            },
+        ),
+        (60, Fault::Heal),
+        (100, Fault::Kill { node: node(0) }),
+        (150, Fault::Restart { node: node(0) }),
+        (200, Fault::Pause { node: node(1) }),
+        (220, Fault::Resume { node: node(1) }),
+    ];
+    let case = Case {
+        plan: Plan {
+            seed: 0,
+            steps: steps
+                .into_iter()
+                .map(|(at, fault)| Step { at, fault })
+                .collect(),
+        },
+        requests: generator::script(&config),
+    };
+    assert!(!run(&case).0.valid);
+
+    let min = shrink(&case, |case| !run(case).0.valid);
+    assert_eq!(min.plan.steps, case.plan.steps[2..4]);
+    // An id before the crash and one after, from the client bound to n0,
+    // requests racing with the restart possibly being kept as well
+    assert!((2..=3).contains(&min.requests.len()));
+    assert!(min.requests.iter().all(|r| r.client % 3 == 0));
+
+    // Runs are timed in real time, so a busy machine may need a few tries
+    let (report, history, trace) = (0..3)
+        .map(|_| run(&min))
+        .find(|(report, ..)| !report.valid)
+        .unwrap();
+    // The window runs from the first request of a duplicate id to the last
+    // reply, leaving out the initial inits but not the restart in between
+    let duplicates = history
+        .ops
+        .iter()
+        .filter(|op| op.ty == Type::Ok && report.duplicated.contains_key(&op.value.to_string()));
+    let (from, to) = span(&history, duplicates.map(|op| op.index)).unwrap();
+    let window = window(&trace, from, to);
+    assert!(window.len() < trace.len());
+    let inits: Vec<&str> = window
+        .iter()
+        .filter(|e| e.msg.body["type"] == "init")
+        .map(|e| e.msg.dest.as_str())
+        .collect();
+    assert_eq!(inits, ["n0"]);
+    let ids: Vec<String> = window
+        .iter()
+        .filter(|e| e.msg.body["type"] == "generate_ok")
+        .map(|e| e.msg.body["id"].to_string())
+        .collect();
+    assert!(
+        report.duplicated.keys().all(|id| ids.contains(id)),
+        "{ids:?}"
+    );
+}
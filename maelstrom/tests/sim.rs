@@ -0,0 +1,347 @@
+use std::{fs, path::PathBuf, process::Command, sync::Mutex};
+
+use serde_json::{json, Value};
+
+fn store(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("maelstrom-sim-{name}-{}", std::process::id()));
+    fs::remove_dir_all(&dir).ok();
+    dir
+}
+
+/// Held by tests running large clusters, whose rates suffer from sharing
+/// the machine with one another
+static LARGE: Mutex<()> = Mutex::new(());
+
+fn sim(args: &[&str], store: &PathBuf) -> Command {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_maelstrom-sim"));
+    cmd.arg("test").args(args).arg("--store").arg(store);
+    cmd
+}
+
+#[test]
+fn echo_binary_passes_and_writes_results() {
+    let store = store("echo");
+    let status = sim(
+        &[
+            "-w",
+            "echo",
+            "--bin",
+            env!("CARGO_BIN_EXE_maelstrom-echo"),
+            "--node-count",
+            "2",
+            "--time-limit",
+            "1",
+            "--rate",
+            "20",
+            "--nemesis",
+            "partition,kill",
+            "--nemesis-interval",
+            "0.2",
+            "--seed",
+            "3",
+        ],
+        &store,
+    )
+    .status()
+    .unwrap();
+    assert!(status.success());
+
+    let latest = store.join("latest");
+    let results: Value =
+        serde_json::from_slice(&fs::read(latest.join("results.json")).unwrap()).unwrap();
+    assert_eq!(results["valid"], true);
+    assert!(results["stats"]["count"].as_u64().unwrap() > 0);
+    for file in [
+        "history.json",
+        "messages.json",
+        "case.json",
+        "node-logs/n1.log",
+    ] {
+        assert!(latest.join(file).exists(), "missing {file}");
+    }
+    let case: Value = serde_json::from_slice(&fs::read(latest.join("case.json")).unwrap()).unwrap();
+    assert!(!case["plan"]["steps"].as_array().unwrap().is_empty());
+    fs::remove_dir_all(store).ok();
+}
+
+#[test]
+fn invalid_results_and_usage_errors_exit_non_zero() {
+    let store = store("invalid");
+    // The echo node crashes on counter requests, leaving no final read
+    let output = sim(
+        &[
+            "-w",
+            "pn-counter",
+            "--bin",
+            env!("CARGO_BIN_EXE_maelstrom-echo"),
+            "--time-limit",
+            "0.5",
+        ],
+        &store,
+    )
+    .output()
+    .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Analysis invalid!"));
+
+    let output = sim(&["-w", "echo", "--bogus", "1"], &store)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown option --bogus"));
+    fs::remove_dir_all(store).ok();
+}
+
+#[test]
+fn shrunk_repros_replay() {
+    let store = store("repro");
+    // The echo node crashes on counter requests, failing with no faults and
+    // no requests at all as the final read crashes it as well
+    let args = [
+        "-w",
+        "pn-counter",
+        "--bin",
+        env!("CARGO_BIN_EXE_maelstrom-echo"),
+        "--time-limit",
+        "0.5",
+        "--nemesis",
+        "kill",
+        "--nemesis-interval",
+        "0.1",
+    ];
+    let output = sim(&[&args[..], &["--shrink"]].concat(), &store)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let repro_path = store
+        .join("latest")
+        .canonicalize()
+        .unwrap()
+        .join("repro.json");
+    let repro: Value = serde_json::from_slice(&fs::read(&repro_path).unwrap()).unwrap();
+    assert_eq!(repro["case"]["plan"]["steps"], json!([]));
+    assert_eq!(repro["case"]["requests"], json!([]));
+
+    let output = sim(
+        &[&args[..], &["--replay", repro_path.to_str().unwrap()]].concat(),
+        &store,
+    )
+    .output()
+    .unwrap();
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Analysis invalid!"));
+    let replayed = store.join("latest").join("case.json");
+    let case: Value = serde_json::from_slice(&fs::read(replayed).unwrap()).unwrap();
+    assert_eq!(case, repro["case"]);
+    fs::remove_dir_all(store).ok();
+}
+
+#[test]
+fn check_compares_with_maelstrom_results() {
+    let store = store("check");
+    fs::create_dir_all(&store).unwrap();
+    // A stale read after a write completed
+    let history = "{:type :invoke, :f :write, :value [0 1], :time 0, :process 0, :index 0}
+{:type :ok, :f :write, :value [0 1], :time 1, :process 0, :index 1}
+{:type :invoke, :f :read, :value [0 nil], :time 2, :process 1, :index 2}
+{:type :ok, :f :read, :value [0 nil], :time 3, :process 1, :index 3}";
+    fs::write(store.join("history.edn"), history).unwrap();
+    fs::write(
+        store.join("results.edn"),
+        "{:workload {:valid? false}, :valid? false}",
+    )
+    .unwrap();
+    let check = |store: &PathBuf| {
+        Command::new(env!("CARGO_BIN_EXE_maelstrom-sim"))
+            .args(["check", "-w", "lin-kv"])
+            .arg(store)
+            .output()
+            .unwrap()
+    };
+    let output = check(&store);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Maelstrom agrees, valid? false"));
+
+    fs::write(store.join("results.edn"), "{:valid? true}").unwrap();
+    let output = check(&store);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Checkers disagree"));
+
+    // Without the read's completion, it may have happened before the write
+    let pending: Vec<&str> = history.lines().take(3).collect();
+    fs::write(store.join("history.edn"), pending.join("\n")).unwrap();
+    fs::remove_file(store.join("results.edn")).unwrap();
+    assert_eq!(check(&store).status.code(), Some(0));
+    fs::remove_dir_all(store).ok();
+}
+
+#[test]
+fn gossip_counter_converges_across_partitions() {
+    let store = store("counter-gossip");
+    let status = sim(
+        &[
+            "-w",
+            "pn-counter",
+            "--bin",
+            env!("CARGO_BIN_EXE_maelstrom-counter"),
+            "--node-count",
+            "5",
+            "--time-limit",
+            "3",
+            "--rate",
+            "50",
+            "--nemesis",
+            "partition",
+            "--nemesis-interval",
+            "0.5",
+        ],
+        &store,
+    )
+    .status()
+    .unwrap();
+    assert!(status.success());
+    fs::remove_dir_all(store).ok();
+}
+
+#[test]
+fn seq_kv_counter_survives_restarts() {
+    let store = store("counter-seq-kv");
+    let status = sim(
+        &[
+            "-w",
+            "pn-counter",
+            "--bin",
+            env!("CARGO_BIN_EXE_maelstrom-counter"),
+            "--node-count",
+            "3",
+            "--time-limit",
+            "3",
+            "--rate",
+            "50",
+            "--nemesis",
+            "kill",
+            "--nemesis-interval",
+            "0.5",
+        ],
+        &store,
+    )
+    .env("COUNTER_MODE", "seq-kv")
+    .status()
+    .unwrap();
+    assert!(status.success());
+    fs::remove_dir_all(store).ok();
+}
+
+#[test]
+fn unique_ids_survive_restarts_in_a_large_cluster() {
+    let _large = LARGE.lock().unwrap_or_else(|e| e.into_inner());
+    let configs = [
+        ("UNIQUE_ID_EPOCH", "storage"),
+        ("UNIQUE_ID_EPOCH", "lin-kv"),
+        ("UNIQUE_ID_FORMAT", "snowflake"),
+        ("UNIQUE_ID_FORMAT", "ulid"),
+        ("UNIQUE_ID_FORMAT", "blocks"),
+    ];
+    for (var, value) in configs {
+        let store = store(&format!("unique-ids-{value}"));
+        let status = sim(
+            &[
+                "-w",
+                "unique-ids",
+                "--bin",
+                env!("CARGO_BIN_EXE_maelstrom-unique-id"),
+                "--node-count",
+                "25",
+                "--time-limit",
+                "3",
+                "--rate",
+                "200",
+                "--nemesis",
+                "kill",
+                "--nemesis-interval",
+                "0.3",
+            ],
+            &store,
+        )
+        .env(var, value)
+        .status()
+        .unwrap();
+        assert!(status.success(), "with {var}={value}");
+        fs::remove_dir_all(store).ok();
+    }
+}
+
+#[test]
+fn batched_broadcast_keeps_msgs_per_op_low() {
+    let _large = LARGE.lock().unwrap_or_else(|e| e.into_inner());
+    let store = store("broadcast");
+    let status = sim(
+        &[
+            "-w",
+            "broadcast",
+            "--bin",
+            env!("CARGO_BIN_EXE_maelstrom-broadcast"),
+            "--node-count",
+            "25",
+            "--time-limit",
+            "3",
+            "--rate",
+            "100",
+            "--latency",
+            "100",
+        ],
+        &store,
+    )
+    .env("BROADCAST_MODE", "batch")
+    .status()
+    .unwrap();
+    assert!(status.success());
+
+    let results: Value =
+        serde_json::from_slice(&fs::read(store.join("latest/results.json")).unwrap()).unwrap();
+    let msgs_per_op = results["net"]["servers"]["msgs-per-op"].as_f64().unwrap();
+    assert!(msgs_per_op < 20., "{msgs_per_op} msgs per op");
+    assert!(results["workload"]["stable_latencies"]["0.5"].is_number());
+    fs::remove_dir_all(store).ok();
+}
+
+#[test]
+fn star_overlay_cuts_broadcast_latency() {
+    let _large = LARGE.lock().unwrap_or_else(|e| e.into_inner());
+    let store = store("overlay");
+    let status = sim(
+        &[
+            "-w",
+            "broadcast",
+            "--bin",
+            env!("CARGO_BIN_EXE_maelstrom-broadcast"),
+            "--node-count",
+            "25",
+            "--time-limit",
+            "3",
+            "--rate",
+            "100",
+            "--latency",
+            "100",
+        ],
+        &store,
+    )
+    .env("BROADCAST_MODE", "batch")
+    .env("BROADCAST_INTERVAL_MS", "50")
+    .env("BROADCAST_OVERLAY", "star")
+    .status()
+    .unwrap();
+    assert!(status.success());
+
+    let results: Value =
+        serde_json::from_slice(&fs::read(store.join("latest/results.json")).unwrap()).unwrap();
+    let msgs_per_op = results["net"]["servers"]["msgs-per-op"].as_f64().unwrap();
+    assert!(msgs_per_op < 20., "{msgs_per_op} msgs per op");
+    // Two hops away at most, against over a second through maelstrom's grid,
+    // as the justfile runs it
+    let median = results["workload"]["stable_latencies"]["0.5"]
+        .as_f64()
+        .unwrap();
+    assert!(median < 400., "median stable latency {median}ms");
+    fs::remove_dir_all(store).ok();
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use std::{
+    fs,
+    io::Write,
+    time::{Duration, Instant},
+};
+
+use common::{Dir, Setup};
+use gossip_glomers::{generator::Client, nemesis::Lifecycle, storage::Storage, Err};
+use serde_json::json;
+use serde_json::Value;
+
+#[test]
+fn saves_replace_values_and_survive_reopening() {
+    let tmp = Dir::new("storage-save");
+    let dir = &tmp.0;
+    let storage = Storage::at(dir).unwrap();
+    assert_eq!(storage.load::<u64>("counter").unwrap(), None);
+    storage.save("counter", &1000u64).unwrap();
+    storage.save("counter", &2000u64).unwrap();
+
+    let reopened = Storage::at(dir).unwrap();
+    assert_eq!(reopened.load::<u64>("counter").unwrap(), Some(2000));
+    let files: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect();
+    assert_eq!(files, ["counter"]);
+}
+
+#[test]
+fn logs_replay_entries_and_drop_a_torn_tail() {
+    let tmp = Dir::new("storage-log");
+    let dir = &tmp.0;
+    let storage = Storage::at(dir).unwrap();
+    let (mut log, entries) = storage.log::<Vec<u64>>("log").unwrap();
+    assert!(entries.is_empty());
+    log.append(&vec![1]).unwrap();
+    log.append_all([&vec![2, 3], &vec![4]]).unwrap();
+    drop(log);
+
+    // Crash in the middle of an append
+    let mut file = fs::OpenOptions::new()
+        .append(true)
+        .open(dir.join("log"))
+        .unwrap();
+    file.write_all(b"[5, 6").unwrap();
+    drop(file);
+
+    let (mut log, entries) = storage.log::<Vec<u64>>("log").unwrap();
+    assert_eq!(entries, [vec![1], vec![2, 3], vec![4]]);
+    log.append(&vec![7]).unwrap();
+    let (_, entries) = storage.log::<Vec<u64>>("log").unwrap();
+    assert_eq!(entries, [vec![1], vec![2, 3], vec![4], vec![7]]);
+}
+
+#[test]
+fn memory_storage_forgets_everything() {
+    let storage = Storage::memory();
+    assert!(!storage.is_durable());
+    storage.save("counter", &1u64).unwrap();
+    assert_eq!(storage.load::<u64>("counter").unwrap(), None);
+    let (mut log, _) = storage.log::<u64>("log").unwrap();
+    log.append(&1).unwrap();
+    assert!(storage.log::<u64>("log").unwrap().1.is_empty());
+}
+
+/// Retry a request until a leader is elected to answer it
+fn until_leader(client: &mut Client, body: Value) -> Result<Value, Err> {
+    let deadline = Instant::now() + Duration::from_secs(30);
+    loop {
+        match client.rpc("n0", body.clone(), Duration::from_secs(1)) {
+            Err(Err::TemporarilyUnavailable | Err::Timeout) if Instant::now() < deadline => {}
+            res => return res,
+        }
+    }
+}
+
+#[test]
+fn restarted_raft_cluster_keeps_committed_writes() {
+    let bin = env!("CARGO_BIN_EXE_maelstrom-raft");
+    let mut setup = Setup::new("raft-restart", bin, 3, true);
+    let write = json!({"type": "write", "key": 1, "value": 42});
+    until_leader(&mut setup.client, write).unwrap();
+
+    for node in ["n0", "n1", "n2"] {
+        setup.cluster.kill(node);
+    }
+    for node in ["n0", "n1", "n2"] {
+        setup.cluster.restart(node);
+    }
+    let sent = setup.cluster.net.trace().len();
+    let read = until_leader(&mut setup.client, json!({"type": "read", "key": 1})).unwrap();
+    assert_eq!(read["value"], 42);
+    // Replaying the write into the state machine does not answer it again
+    let trace = setup.cluster.net.trace();
+    assert!(trace[sent..]
+        .iter()
+        .all(|e| e.msg.body["type"] != "write_ok"));
+}
@@ -0,0 +1,95 @@
+use gossip_glomers::topology::Topology;
+
+fn ids(n: usize) -> Vec<String> {
+    (0..n).map(|i| format!("n{i}")).collect()
+}
+
+#[test]
+fn random_regular_graphs_are_regular_and_connected() {
+    for (n, k) in [(20, 4), (10, 3), (8, 2)] {
+        for seed in 0..5 {
+            let stats = Topology::random_regular(&ids(n), k, seed).stats();
+            assert_eq!((stats.min_fanout, stats.max_fanout), (k, k));
+            assert_eq!(stats.edges, n * k / 2);
+            assert!(stats.diameter.is_some(), "{n} nodes, k = {k}, seed {seed}");
+        }
+    }
+}
+
+#[test]
+fn fixed_shapes_have_their_diameter_and_fanout() {
+    // (graph, edges, diameter, min and max fanout)
+    let cases = [
+        (Topology::star(&ids(6), "n0"), 5, 2, 1, 5),
+        (Topology::tree(&ids(7), 2), 6, 4, 1, 3),
+        (Topology::ring(&ids(6)), 6, 3, 2, 2),
+        (Topology::grid(&ids(9)), 12, 4, 2, 4),
+    ];
+    for (graph, edges, diameter, min, max) in cases {
+        let stats = graph.stats();
+        assert_eq!(stats.nodes, graph.nodes().count());
+        assert_eq!(stats.edges, edges, "{graph:?}");
+        assert_eq!(stats.diameter, Some(diameter), "{graph:?}");
+        assert_eq!(
+            (stats.min_fanout, stats.max_fanout),
+            (min, max),
+            "{graph:?}"
+        );
+        assert_eq!(stats.mean_fanout, 2. * edges as f64 / stats.nodes as f64);
+    }
+}
+
+#[test]
+fn two_level_trees_reach_everyone_in_two_hops() {
+    for n in [1, 2, 5, 25, 100] {
+        let graph = Topology::two_level(&ids(n));
+        let dist = graph.distances("n0");
+        assert_eq!(dist.len(), n);
+        assert!(dist.values().all(|d| *d <= 2), "{n} nodes");
+    }
+    // 1 + 5 + 25 >= 25 > 1 + 4 + 16
+    assert_eq!(Topology::two_level(&ids(25)).stats().max_fanout, 6);
+}
+
+#[test]
+fn spanning_trees_keep_shortest_paths_from_the_root() {
+    let graph = Topology::grid(&ids(16));
+    let tree = graph.spanning_tree("n5");
+    let stats = tree.stats();
+    assert_eq!(stats.edges, 15);
+    assert!(stats.diameter.is_some());
+    assert_eq!(tree.distances("n5"), graph.distances("n5"));
+    for id in tree.nodes() {
+        for next in tree.neighbours(id) {
+            assert!(graph.neighbours(id).any(|n| n == next));
+        }
+    }
+}
+
+#[test]
+fn disconnected_graphs_have_no_diameter() {
+    let mut graph = Topology::empty(&ids(4));
+    graph.link("n0", "n1");
+    graph.link("n2", "n2");
+    let stats = graph.stats();
+    assert_eq!((stats.edges, stats.diameter), (1, None));
+    assert_eq!((stats.min_fanout, stats.max_fanout), (0, 1));
+    assert_eq!(graph.distances("n0").len(), 2);
+}
+
+#[test]
+fn small_worlds_keep_the_lattice_edge_count() {
+    let graph = Topology::small_world(&ids(30), 4, 0.2, 7);
+    let stats = graph.stats();
+    assert_eq!(stats.edges, 60);
+    assert_eq!(stats.mean_fanout, 4.);
+    // Without rewiring, the ring lattice itself
+    let lattice = Topology::small_world(&ids(6), 2, 0., 0);
+    assert_eq!(lattice, Topology::ring(&ids(6)));
+}
+
+#[test]
+#[should_panic(expected = "at least two neighbours")]
+fn small_worlds_need_a_lattice() {
+    Topology::small_world(&ids(10), 1, 0.1, 0);
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use std::collections::BTreeSet;
+
+use common::Setup;
+use gossip_glomers::{nemesis::Lifecycle, Err, KV};
+use serde_json::json;
+
+const BIN: &str = env!("CARGO_BIN_EXE_maelstrom-unique-id");
+
+#[test]
+fn restarted_node_never_repeats_ids() {
+    let mut setup = Setup::new("unique-id-restart", BIN, 1, true);
+    let mut ids = BTreeSet::new();
+    for _ in 0..2 {
+        for _ in 0..10 {
+            let reply = setup.rpc("n0", json!({"type": "generate"}));
+            assert!(ids.insert(reply.unwrap()["id"].to_string()));
+        }
+        setup.cluster.kill("n0");
+        setup.cluster.restart("n0");
+    }
+    assert_eq!(ids.len(), 20);
+}
+
+#[test]
+fn batches_never_overlap_across_restarts() {
+    let mut setup = Setup::new("unique-id-batch", BIN, 2, true);
+    let mut ids = BTreeSet::new();
+    for _ in 0..2 {
+        for node in ["n0", "n1"] {
+            let body = json!({"type": "generate_batch", "count": 100});
+            let reply = setup.rpc(node, body).unwrap();
+            for id in reply["ids"].as_array().unwrap() {
+                assert!(ids.insert(id.to_string()), "{id} twice");
+            }
+            let reply = setup.rpc(node, json!({"type": "generate"}));
+            assert!(ids.insert(reply.unwrap()["id"].to_string()));
+        }
+        setup.cluster.kill("n0");
+        setup.cluster.restart("n0");
+    }
+    assert_eq!(ids.len(), 404);
+    let malformed = setup.rpc("n1", json!({"type": "generate_batch"}));
+    assert!(matches!(malformed, Err(Err::MalformedRequest)));
+    // Rather than allocating them all, or wrapping the counter around
+    for count in [1_000_000, u64::MAX] {
+        let body = json!({"type": "generate_batch", "count": count});
+        let too_many = setup.rpc("n1", body);
+        assert!(matches!(too_many, Err(Err::MalformedRequest)));
+    }
+    let reply = setup.rpc("n1", json!({"type": "generate"}));
+    assert!(ids.insert(reply.unwrap()["id"].to_string()));
+}
+
+#[test]
+fn node_without_state_takes_its_epochs_from_lin_kv() {
+    let mut setup = Setup::new("unique-id-kv", BIN, 2, false);
+    setup.cluster.net.serve(KV::Lin);
+    let mut ids = BTreeSet::new();
+    for _ in 0..3 {
+        for node in ["n0", "n1"] {
+            for _ in 0..5 {
+                let reply = setup.rpc(node, json!({"type": "generate"}));
+                assert!(ids.insert(reply.unwrap()["id"].to_string()));
+            }
+        }
+        setup.cluster.kill("n0");
+        setup.cluster.restart("n0");
+    }
+    assert_eq!(ids.len(), 30);
+    assert!(ids.contains("\"n0-2-0\""), "{ids:?}");
+}
@@ -0,0 +1,148 @@
+use gossip_glomers::{
+    checker::{broadcast, kafka, pn_counter, unique_ids},
+    history::{History, Type},
+};
+use serde_json::{json, Value};
+
+/// Build a history from `(process, type, f, value)` events
+fn history(events: &[(u64, Type, &str, Value)]) -> History {
+    let mut history = History::new();
+    for (process, ty, f, value) in events {
+        history.push(*process, *ty, f, value.clone());
+    }
+    history
+}
+
+#[test]
+fn duplicate_ids() {
+    use Type::*;
+    let h = history(&[
+        (0, Invoke, "generate", json!(null)),
+        (1, Invoke, "generate", json!(null)),
+        (0, Ok, "generate", json!("n0-1")),
+        (1, Ok, "generate", json!("n1-1")),
+        (0, Invoke, "generate", json!(null)),
+        (0, Ok, "generate", json!("n1-1")),
+        (1, Invoke, "generate", json!(null)),
+    ]);
+    let report = unique_ids::check(&h);
+    assert!(!report.valid);
+    assert_eq!((report.attempted, report.acknowledged), (4, 3));
+    assert_eq!(report.duplicated.get("\"n1-1\""), Some(&2));
+}
+
+#[test]
+fn lost_broadcast() {
+    use Type::*;
+    let h = history(&[
+        (0, Invoke, "broadcast", json!(1)),
+        (0, Ok, "broadcast", json!(null)),
+        (1, Invoke, "broadcast", json!(2)),
+        (1, Ok, "broadcast", json!(null)),
+        // Indeterminate messages may or may not show up
+        (0, Invoke, "broadcast", json!(3)),
+        (0, Info, "broadcast", json!(null)),
+        (2, Invoke, "read", json!(null)),
+        (2, Ok, "read", json!([1])),
+        (2, Invoke, "read", json!(null)),
+        (2, Ok, "read", json!([1, 2, 3])),
+        (3, Invoke, "read", json!(null)),
+        (3, Ok, "read", json!([1])),
+    ]);
+    let report = broadcast::check(&h);
+    assert!(!report.valid);
+    assert_eq!(report.lost, [json!(2)]);
+    assert_eq!(report.recovered, 1);
+    assert!(report.unexpected.is_empty());
+}
+
+#[test]
+fn rebroadcast_keeps_the_acknowledgement() {
+    use Type::*;
+    let h = history(&[
+        (0, Invoke, "broadcast", json!(1)),
+        (0, Ok, "broadcast", json!(null)),
+        (0, Invoke, "broadcast", json!(1)),
+        (0, Info, "broadcast", json!(null)),
+        (0, Invoke, "broadcast", json!(1)),
+        (0, Fail, "broadcast", json!(null)),
+        (1, Invoke, "broadcast", json!(2)),
+        (1, Info, "broadcast", json!(null)),
+        (1, Invoke, "broadcast", json!(2)),
+        (1, Ok, "broadcast", json!(null)),
+        (2, Invoke, "read", json!(null)),
+        (2, Ok, "read", json!([])),
+    ]);
+    let report = broadcast::check(&h);
+    assert!(!report.valid);
+    assert_eq!(report.lost, [json!(1), json!(2)]);
+    assert_eq!(report.acknowledged, 2);
+}
+
+#[test]
+fn json_broadcasts() {
+    use Type::*;
+    let h = history(&[
+        (0, Invoke, "broadcast", json!({"user": "a", "text": "hi"})),
+        (0, Ok, "broadcast", json!(null)),
+        (1, Invoke, "broadcast", json!("plain")),
+        (1, Ok, "broadcast", json!(null)),
+        (2, Invoke, "read", json!(null)),
+        (2, Ok, "read", json!([{"text": "hi", "user": "a"}, [1]])),
+    ]);
+    let report = broadcast::check(&h);
+    assert!(!report.valid);
+    assert_eq!(report.lost, [json!("plain")]);
+    assert_eq!(report.unexpected, [json!([1])]);
+    assert_eq!(report.acknowledged, 2);
+}
+
+#[test]
+fn counter_bounds() {
+    use Type::*;
+    let mut events = vec![
+        (0, Invoke, "add", json!(5)),
+        (0, Ok, "add", json!(5)),
+        (1, Invoke, "add", json!(-2)),
+        (1, Info, "add", json!(-2)),
+        (2, Invoke, "add", json!(3)),
+        (2, Info, "add", json!(3)),
+        (0, Invoke, "read", json!(null)),
+        (0, Ok, "read", json!(6)),
+        (3, Invoke, "read", json!(null)),
+        (3, Ok, "read", json!(8)),
+    ];
+    let report = pn_counter::check(&history(&events));
+    assert!(report.valid);
+    assert_eq!(report.bounds, (3, 8));
+
+    events.push((0, Invoke, "read", json!(null)));
+    events.push((0, Ok, "read", json!(9)));
+    let report = pn_counter::check(&history(&events));
+    assert!(!report.valid);
+    assert_eq!(report.errors, [9]);
+}
+
+#[test]
+fn kafka_anomalies() {
+    use Type::*;
+    let h = history(&[
+        (0, Invoke, "send", json!(["k", 10])),
+        (0, Ok, "send", json!(["k", 10, 0])),
+        (0, Invoke, "send", json!(["k", 11])),
+        (0, Ok, "send", json!(["k", 11, 1])),
+        (0, Invoke, "send", json!(["k", 12])),
+        (0, Ok, "send", json!(["k", 12, 2])),
+        (1, Invoke, "poll", json!({"k": 0})),
+        (1, Ok, "poll", json!({"k": [[0, 10], [2, 12]]})),
+        (2, Invoke, "poll", json!({"k": 2})),
+        (2, Ok, "poll", json!({"k": [[2, 13]]})),
+    ]);
+    let report = kafka::check(&h);
+    assert!(!report.valid);
+    assert_eq!(report.lost, [("k".to_owned(), 1, 11)]);
+    assert_eq!(report.gaps.len(), 1);
+    assert_eq!(report.gaps[0].skipped, [1]);
+    assert_eq!(report.inconsistent["k"].len(), 1);
+    assert!(report.nonmonotonic.is_empty());
+}